@@ -1,11 +1,67 @@
-use crate::rand::MersenneTwister;
+use crate::generator::GeneratorParams;
+use crate::rand::{GeneratorVersion, MapRng, RangeError};
 use core::fmt;
+use cosmwasm_std::Binary;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use thiserror::Error;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MapError {
+    #[error("Size of a BSP_Map needs to be greater than or equal {}", MIN_MAP_SIZE)]
+    MapTooSmall,
+    #[error("Size of a map needs to be less than or equal {}", MAX_MAP_SIZE)]
+    MapTooLarge,
+    #[error(
+        "Minimum room size needs to be greater than or equal {}",
+        MIN_ROOM_SIZE
+    )]
+    MinRoomTooSmall,
+    #[error("Minimum room size (width) needs to be less than maximum room size (width).")]
+    MinRoomWidthTooLarge,
+    #[error("Minimum room size (height) needs to be less than maximum room size (height).")]
+    MinRoomHeightTooLarge,
+    #[error("Maximum room size (width) must be less than map size (width).")]
+    MaxRoomWidthTooLarge,
+    #[error("Maximum room size (height) must be less than map size (height).")]
+    MaxRoomHeightTooLarge,
+    #[error(transparent)]
+    EmptyRange(#[from] RangeError),
+}
+
+#[derive(
+    Serialize, Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum Tile {
     Floor,
     Wall,
+    // outside of any room or corridor
+    Void,
+    // overworld biomes from the noise generator
+    Water,
+    Sand,
+    Grass,
+    Forest,
+    Mountain,
+}
+
+impl Tile {
+    #[must_use]
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '0' => Some(Tile::Floor),
+            '1' => Some(Tile::Wall),
+            'x' => Some(Tile::Void),
+            '~' => Some(Tile::Water),
+            ':' => Some(Tile::Sand),
+            ',' => Some(Tile::Grass),
+            'T' => Some(Tile::Forest),
+            '^' => Some(Tile::Mountain),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Tile {
@@ -13,11 +69,17 @@ impl fmt::Display for Tile {
         match self {
             Tile::Floor => write!(f, "0"),
             Tile::Wall => write!(f, "1"),
+            Tile::Void => write!(f, "x"),
+            Tile::Water => write!(f, "~"),
+            Tile::Sand => write!(f, ":"),
+            Tile::Grass => write!(f, ","),
+            Tile::Forest => write!(f, "T"),
+            Tile::Mountain => write!(f, "^"),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash, JsonSchema)]
 pub struct Point {
     x: u32,
     y: u32,
@@ -28,6 +90,16 @@ impl Point {
     pub fn new(x: u32, y: u32) -> Self {
         Point { x, y }
     }
+
+    #[must_use]
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    #[must_use]
+    pub fn y(&self) -> u32 {
+        self.y
+    }
 }
 
 impl fmt::Display for Point {
@@ -37,7 +109,7 @@ impl fmt::Display for Point {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash, JsonSchema)]
 pub struct Size {
     width: u32,
     height: u32,
@@ -45,12 +117,35 @@ pub struct Size {
 
 impl Size {
     #[must_use]
-    pub fn new(width: u32, height: u32) -> Self {
+    pub const fn new(width: u32, height: u32) -> Self {
         Size { width, height }
     }
+
+    #[must_use]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[must_use]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // whether neither side is longer than that of `max`
+    #[must_use]
+    pub fn fits_within(&self, max: Size) -> bool {
+        self.width <= max.width && self.height <= max.height
+    }
 }
 
-#[derive(Clone, Copy)]
+// as the limits are spelled out in errors
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "width : {}, height : {}", self.width, self.height)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, JsonSchema)]
 pub struct Rectangle {
     position: Point,
     size: Size,
@@ -62,6 +157,16 @@ impl Rectangle {
         Rectangle { position, size }
     }
 
+    #[must_use]
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    #[must_use]
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
     #[must_use]
     pub fn intersects(&self, other: &Rectangle) -> bool {
         let x_intersect: bool = ((self.position.x + self.size.width) > other.position.x)
@@ -96,12 +201,12 @@ impl TreeNode {
 
     fn split(
         &mut self,
-        rng: &mut MersenneTwister,
+        rng: &mut impl MapRng,
         min_room_size: Size,
         max_room_size: Size,
-    ) -> bool {
+    ) -> Result<bool, MapError> {
         if self.left_child.is_some() || self.right_child.is_some() {
-            return false;
+            return Ok(false);
         }
 
         let split_horizontal: bool;
@@ -113,13 +218,13 @@ impl TreeNode {
         {
             split_horizontal = true;
         } else {
-            split_horizontal = rng.generate_range(0, 1) == 1;
+            split_horizontal = rng.range(0, 1) == 1;
         }
 
         let side_length = if split_horizontal {
-            rng.generate_range(min_room_size.height, max_room_size.height)
+            rng.try_range(min_room_size.height, max_room_size.height)?
         } else {
-            rng.generate_range(min_room_size.width, max_room_size.width)
+            rng.try_range(min_room_size.width, max_room_size.width)?
         };
 
         if split_horizontal {
@@ -127,7 +232,7 @@ impl TreeNode {
                 || side_length < min_room_size.height
                 || self.size.height - side_length < min_room_size.height
             {
-                return false;
+                return Ok(false);
             }
 
             self.left_child = Some(Box::new(TreeNode::new(
@@ -143,7 +248,7 @@ impl TreeNode {
                 || side_length < min_room_size.width
                 || self.size.width - side_length < min_room_size.width
             {
-                return false;
+                return Ok(false);
             }
 
             self.left_child = Some(Box::new(TreeNode::new(
@@ -156,51 +261,57 @@ impl TreeNode {
             )));
         }
 
-        true
+        Ok(true)
     }
 
     fn is_leaf(&self) -> bool {
         self.left_child.is_none() && self.right_child.is_none()
     }
 
-    fn generate(&mut self, rng: &mut MersenneTwister, min_room_size: Size, max_room_size: Size) {
-        if self.is_leaf() && self.split(rng, min_room_size, max_room_size) {
-            self.left_child
-                .as_mut()
-                .unwrap()
-                .generate(rng, min_room_size, max_room_size);
-            self.right_child
-                .as_mut()
-                .unwrap()
-                .generate(rng, min_room_size, max_room_size);
+    fn generate(
+        &mut self,
+        rng: &mut impl MapRng,
+        min_room_size: Size,
+        max_room_size: Size,
+    ) -> Result<(), MapError> {
+        if self.is_leaf() && self.split(rng, min_room_size, max_room_size)? {
+            if let Some(left) = self.left_child.as_mut() {
+                left.generate(rng, min_room_size, max_room_size)?;
+            }
+            if let Some(right) = self.right_child.as_mut() {
+                right.generate(rng, min_room_size, max_room_size)?;
+            }
         }
+        Ok(())
     }
 
-    fn create_rooms(&mut self, rng: &mut MersenneTwister) {
+    fn create_rooms(&mut self, rng: &mut impl MapRng) -> Result<(), MapError> {
         if self.left_child.is_some() || self.right_child.is_some() {
-            if self.left_child.is_some() {
-                self.left_child.as_mut().unwrap().create_rooms(rng);
+            if let Some(left) = self.left_child.as_mut() {
+                left.create_rooms(rng)?;
             }
-            if self.right_child.is_some() {
-                self.right_child.as_mut().unwrap().create_rooms(rng);
+            if let Some(right) = self.right_child.as_mut() {
+                right.create_rooms(rng)?;
             }
 
-            if self.left_child.is_some() && self.right_child.is_some() {
-                let l_room = self.left_child.as_ref().unwrap().get_room(rng);
-                let r_room = self.right_child.as_ref().unwrap().get_room(rng);
+            let rooms = match (&self.left_child, &self.right_child) {
+                (Some(left), Some(right)) => (left.get_room(rng), right.get_room(rng)),
+                _ => (None, None),
+            };
 
-                if let (Some(l), Some(r)) = (l_room, r_room) {
-                    self.create_hall(rng, l, r);
-                }
+            if let (Some(l), Some(r)) = rooms {
+                self.create_hall(rng, l, r)?;
             }
         } else {
+            // leaves are never smaller than the minimum room size, so a room and the
+            // cell around it always fit
             let room_size = Size::new(
-                rng.generate_range(3, self.size.width - 2),
-                rng.generate_range(3, self.size.height - 2),
+                rng.try_range(3, self.size.width - 2)?,
+                rng.try_range(3, self.size.height - 2)?,
             );
             let room_pos = Point::new(
-                rng.generate_range(1, self.size.width - room_size.width - 1),
-                rng.generate_range(1, self.size.height - room_size.height - 1),
+                rng.try_range(1, self.size.width - room_size.width - 1)?,
+                rng.try_range(1, self.size.height - room_size.height - 1)?,
             );
 
             self.room = Some(Rectangle::new(
@@ -208,21 +319,20 @@ impl TreeNode {
                 room_size,
             ));
         }
+        Ok(())
     }
 
-    fn get_room(&self, rng: &mut MersenneTwister) -> Option<Rectangle> {
+    fn get_room(&self, rng: &mut impl MapRng) -> Option<Rectangle> {
         if let Some(r) = self.room {
             Some(r)
         } else {
-            let l_room = if self.left_child.is_some() {
-                self.left_child.as_ref().unwrap().get_room(rng)
-            } else {
-                None
+            let l_room = match &self.left_child {
+                Some(left) => left.get_room(rng),
+                None => None,
             };
-            let r_room = if self.right_child.is_some() {
-                self.right_child.as_ref().unwrap().get_room(rng)
-            } else {
-                None
+            let r_room = match &self.right_child {
+                Some(right) => right.get_room(rng),
+                None => None,
             };
 
             if l_room.is_none() && r_room.is_none() {
@@ -231,7 +341,7 @@ impl TreeNode {
                 l_room
             } else if l_room.is_none() {
                 r_room
-            } else if rng.generate_range(0, 1) == 1 {
+            } else if rng.range(0, 1) == 1 {
                 l_room
             } else {
                 r_room
@@ -239,224 +349,264 @@ impl TreeNode {
         }
     }
 
-    fn create_hall(&mut self, rng: &mut MersenneTwister, l_room: Rectangle, r_room: Rectangle) {
+    fn create_hall(
+        &mut self,
+        rng: &mut impl MapRng,
+        l_room: Rectangle,
+        r_room: Rectangle,
+    ) -> Result<(), MapError> {
         self.corridors.clear();
 
         let point1 = Point::new(
-            rng.generate_range(
+            rng.try_range(
                 l_room.position.x + 1,
                 l_room.position.x + l_room.size.width - 2,
-            ),
-            rng.generate_range(
+            )?,
+            rng.try_range(
                 l_room.position.y + 1,
                 l_room.position.y + l_room.size.height - 2,
-            ),
+            )?,
         );
         let point2 = Point::new(
-            rng.generate_range(
+            rng.try_range(
                 r_room.position.x + 1,
                 r_room.position.x + r_room.size.width - 2,
-            ),
-            rng.generate_range(
+            )?,
+            rng.try_range(
                 r_room.position.y + 1,
                 r_room.position.y + r_room.size.height - 2,
-            ),
+            )?,
         );
         let width = i64::from(point2.x) - i64::from(point1.x);
         let heigh = i64::from(point2.y) - i64::from(point1.y);
 
         if width < 0 {
             if heigh < 0 {
-                if rng.generate_range(0, 1) == 1 {
+                if rng.range(0, 1) == 1 {
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point1.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point2.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 } else {
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point2.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point2.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 };
             } else if heigh > 0 {
-                if rng.generate_range(0, 1) == 1 {
+                if rng.range(0, 1) == 1 {
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point1.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point1.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 } else {
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point2.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point1.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 };
             } else {
                 self.corridors.push(Rectangle::new(
                     Point::new(point2.x, point2.y),
-                    Size::new(width.abs() as u32, 1),
+                    Size::new(width.unsigned_abs() as u32, 1),
                 ));
             };
         } else if width > 0 {
             if heigh < 0 {
-                if rng.generate_range(0, 1) == 1 {
+                if rng.range(0, 1) == 1 {
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point2.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point2.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 } else {
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point1.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point2.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 };
             } else if heigh > 0 {
-                if rng.generate_range(0, 1) == 1 {
+                if rng.range(0, 1) == 1 {
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point1.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point2.x, point1.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 } else {
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point2.y),
-                        Size::new(width.abs() as u32, 1),
+                        Size::new(width.unsigned_abs() as u32, 1),
                     ));
                     self.corridors.push(Rectangle::new(
                         Point::new(point1.x, point1.y),
-                        Size::new(1, heigh.abs() as u32),
+                        Size::new(1, heigh.unsigned_abs() as u32),
                     ));
                 };
             } else {
                 self.corridors.push(Rectangle::new(
                     Point::new(point1.x, point1.y),
-                    Size::new(width.abs() as u32, 1),
+                    Size::new(width.unsigned_abs() as u32, 1),
                 ));
             };
         } else if heigh < 0 {
             self.corridors.push(Rectangle::new(
                 Point::new(point2.x, point2.y),
-                Size::new(1, heigh.abs() as u32),
+                Size::new(1, heigh.unsigned_abs() as u32),
             ));
         } else if heigh > 0 {
             self.corridors.push(Rectangle::new(
                 Point::new(point1.x, point1.y),
-                Size::new(1, heigh.abs() as u32),
+                Size::new(1, heigh.unsigned_abs() as u32),
             ));
         }
+        Ok(())
     }
 
-    fn iter(&self) -> TreeNodeIterator {
-        TreeNodeIterator::new(&self)
+    fn iter(&self) -> TreeNodeIterator<'_> {
+        TreeNodeIterator::new(self)
+    }
+}
+
+// the largest map any generator builds, keeping generation within gas
+pub const MAX_MAP_SIZE: Size = Size::new(128, 128);
+
+pub const MIN_MAP_SIZE: Size = Size::new(20, 20);
+pub const MIN_ROOM_SIZE: Size = Size::new(6, 6);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+pub struct MapParams {
+    pub size: Size,
+    pub min_room_size: Size,
+    pub max_room_size: Size,
+}
+
+impl MapParams {
+    pub fn validate(&self) -> Result<(), MapError> {
+        let MapParams {
+            size,
+            min_room_size,
+            max_room_size,
+        } = self;
+
+        if !MIN_MAP_SIZE.fits_within(*size) {
+            return Err(MapError::MapTooSmall);
+        }
+        if !size.fits_within(MAX_MAP_SIZE) {
+            return Err(MapError::MapTooLarge);
+        }
+        if !MIN_ROOM_SIZE.fits_within(*min_room_size) {
+            return Err(MapError::MinRoomTooSmall);
+        }
+        if min_room_size.width >= max_room_size.width {
+            return Err(MapError::MinRoomWidthTooLarge);
+        }
+        if min_room_size.height >= max_room_size.height {
+            return Err(MapError::MinRoomHeightTooLarge);
+        }
+        if max_room_size.width >= size.width {
+            return Err(MapError::MaxRoomWidthTooLarge);
+        }
+        if max_room_size.height >= size.height {
+            return Err(MapError::MaxRoomHeightTooLarge);
+        }
+        Ok(())
     }
 }
 
 pub struct BSPMap {
     size: Size,
     tiles: HashMap<Point, Tile>,
+    rooms: Vec<Rectangle>,
+    corridors: Vec<Rectangle>,
     min_room_size: Size,
     max_room_size: Size,
 }
 
 impl BSPMap {
+    pub fn from_params(params: &MapParams, seed: impl MapRng) -> Result<Self, MapError> {
+        BSPMap::new(
+            params.size,
+            seed,
+            params.min_room_size,
+            params.max_room_size,
+        )
+    }
+
     pub fn new(
         size: Size,
-        mut seed: MersenneTwister,
+        mut seed: impl MapRng,
         min_room_size: Size,
         max_room_size: Size,
-    ) -> Result<Self, String> {
-        if size.width < 20 || size.height < 20 {
-            return Err(
-                "Size of a BSP_Map needs to be greater than or equal width : 20, height : 20"
-                    .to_string(),
-            );
-        }
-        if min_room_size.width < 6 || min_room_size.height < 6 {
-            return Err(
-                "Minimum room size needs to be greater than or equal width : 6, height : 6"
-                    .to_string(),
-            );
-        }
-        if min_room_size.width >= max_room_size.width {
-            return Err(
-                "Minimum room size (width) needs to be less than maximum room size (width)."
-                    .to_string(),
-            );
-        }
-        if min_room_size.height >= max_room_size.height {
-            return Err(
-                "Minimum room size (height) needs to be less than maximum room size (height)."
-                    .to_string(),
-            );
-        }
-        if max_room_size.width >= size.width {
-            return Err(
-                "Maximum room size (width) must be less than map size (width).".to_string(),
-            );
-        }
-        if max_room_size.height >= size.height {
-            return Err(
-                "Maximum room size (height) must be less than map size (height).".to_string(),
-            );
+    ) -> Result<Self, MapError> {
+        MapParams {
+            size,
+            min_room_size,
+            max_room_size,
         }
+        .validate()?;
 
         let mut map = BSPMap {
             size,
             tiles: HashMap::new(),
+            rooms: Vec::new(),
+            corridors: Vec::new(),
             min_room_size,
             max_room_size,
         };
 
-        map.place_rooms(&mut seed);
+        map.place_rooms(&mut seed)?;
 
         map.init_walls();
 
         Ok(map)
     }
 
-    fn place_rooms(&mut self, rng: &mut MersenneTwister) {
+    fn place_rooms(&mut self, rng: &mut impl MapRng) -> Result<(), MapError> {
         let mut root = TreeNode::new(Point { x: 0, y: 0 }, self.size);
 
-        root.generate(rng, self.min_room_size, self.max_room_size);
-        root.create_rooms(rng);
+        root.generate(rng, self.min_room_size, self.max_room_size)?;
+        root.create_rooms(rng)?;
 
         for node in root.iter() {
             if node.is_leaf() {
                 if let Some(room) = node.get_room(rng) {
                     self.add_room(&room);
+                    self.rooms.push(room);
                 }
             }
 
             for corridor in &node.corridors {
                 self.add_room(corridor);
+                self.corridors.push(*corridor);
             }
         }
+        Ok(())
     }
 
     fn add_room(&mut self, room: &Rectangle) {
@@ -486,65 +636,43 @@ impl BSPMap {
         let mut walls: Vec<Point> = Vec::new();
 
         for tile in &self.tiles {
-            if self
-                .tiles
-                .get(&Point::new(tile.0.x + 1, tile.0.y))
-                .is_none()
-            {
+            if !self.tiles.contains_key(&Point::new(tile.0.x + 1, tile.0.y)) {
                 walls.push(Point::new(tile.0.x + 1, tile.0.y));
             }
-            if self
+            if !self
                 .tiles
-                .get(&Point::new(tile.0.x + 1, tile.0.y + 1))
-                .is_none()
+                .contains_key(&Point::new(tile.0.x + 1, tile.0.y + 1))
             {
                 walls.push(Point::new(tile.0.x + 1, tile.0.y + 1));
             }
-            if self
-                .tiles
-                .get(&Point::new(tile.0.x, tile.0.y + 1))
-                .is_none()
-            {
+            if !self.tiles.contains_key(&Point::new(tile.0.x, tile.0.y + 1)) {
                 walls.push(Point::new(tile.0.x, tile.0.y + 1));
             }
             if tile.0.x != 0
-                && self
+                && !self
                     .tiles
-                    .get(&Point::new(tile.0.x - 1, tile.0.y + 1))
-                    .is_none()
+                    .contains_key(&Point::new(tile.0.x - 1, tile.0.y + 1))
             {
                 walls.push(Point::new(tile.0.x - 1, tile.0.y + 1));
             }
-            if tile.0.x != 0
-                && self
-                    .tiles
-                    .get(&Point::new(tile.0.x - 1, tile.0.y))
-                    .is_none()
-            {
+            if tile.0.x != 0 && !self.tiles.contains_key(&Point::new(tile.0.x - 1, tile.0.y)) {
                 walls.push(Point::new(tile.0.x - 1, tile.0.y));
             }
             if tile.0.x != 0
                 && tile.0.y != 0
-                && self
+                && !self
                     .tiles
-                    .get(&Point::new(tile.0.x - 1, tile.0.y - 1))
-                    .is_none()
+                    .contains_key(&Point::new(tile.0.x - 1, tile.0.y - 1))
             {
                 walls.push(Point::new(tile.0.x - 1, tile.0.y - 1));
             }
-            if tile.0.y != 0
-                && self
-                    .tiles
-                    .get(&Point::new(tile.0.x, tile.0.y - 1))
-                    .is_none()
-            {
+            if tile.0.y != 0 && !self.tiles.contains_key(&Point::new(tile.0.x, tile.0.y - 1)) {
                 walls.push(Point::new(tile.0.x, tile.0.y - 1));
             }
             if tile.0.y != 0
-                && self
+                && !self
                     .tiles
-                    .get(&Point::new(tile.0.x + 1, tile.0.y - 1))
-                    .is_none()
+                    .contains_key(&Point::new(tile.0.x + 1, tile.0.y - 1))
             {
                 walls.push(Point::new(tile.0.x + 1, tile.0.y - 1));
             }
@@ -556,14 +684,294 @@ impl BSPMap {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    // one line per y, one character per x
+    RowMajor,
+    // one line per x, one character per y (the layout maps were first stored in)
+    ColumnMajor,
+}
+
+impl BSPMap {
+    #[must_use]
+    pub fn rooms(&self) -> &[Rectangle] {
+        &self.rooms
+    }
+
+    #[must_use]
+    pub fn corridors(&self) -> &[Rectangle] {
+        &self.corridors
+    }
+
+    // the map as a row-major grid, cells outside any room or corridor are `Tile::Void`
+    #[must_use]
+    pub fn grid(&self) -> TileGrid {
+        let mut grid = TileGrid::new(self.size, Tile::Void);
+        for (point, tile) in &self.tiles {
+            grid.set(*point, *tile);
+        }
+        grid
+    }
+
+    #[must_use]
+    pub fn render(&self, orientation: Orientation) -> String {
+        let (lines, chars) = match orientation {
+            Orientation::RowMajor => (self.size.height, self.size.width),
+            Orientation::ColumnMajor => (self.size.width, self.size.height),
+        };
+
+        let mut rendered = String::with_capacity(((chars + 1) * lines) as usize);
+        for line in 0..lines {
+            for char in 0..chars {
+                let point = match orientation {
+                    Orientation::RowMajor => Point::new(char, line),
+                    Orientation::ColumnMajor => Point::new(line, char),
+                };
+                let tile = self.tiles.get(&point).copied().unwrap_or(Tile::Void);
+                rendered.push_str(&tile.to_string());
+            }
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
 impl fmt::Display for BSPMap {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for row in 0..self.size.width {
-            for col in 0..self.size.height {
-                match self.tiles.get(&Point::new(row, col)) {
-                    Some(x) => write!(f, "{x}")?,
-                    None => write!(f, "x")?,
+        write!(f, "{}", self.render(Orientation::RowMajor))
+    }
+}
+
+// a map in structured form, so clients don't have to parse the rendered text
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct MapData {
+    pub width: u32,
+    pub height: u32,
+    // row-major, the tile at (x, y) is `tiles[y * width + x]`
+    pub tiles: Vec<Tile>,
+    pub rooms: Vec<Rectangle>,
+    pub corridors: Vec<Rectangle>,
+    // unknown for maps stored before seeds were recorded
+    pub seed: Option<Binary>,
+    pub generator_version: Option<GeneratorVersion>,
+    // only known for maps stored as seed and parameters
+    pub generator: Option<GeneratorParams>,
+}
+
+impl MapData {
+    // a map with tiles only, geometry and seed are filled in by the caller when known
+    #[must_use]
+    pub fn from_grid(grid: TileGrid) -> Self {
+        MapData {
+            width: grid.width,
+            height: grid.height,
+            tiles: grid.tiles,
+            rooms: vec![],
+            corridors: vec![],
+            seed: None,
+            generator_version: None,
+            generator: None,
+        }
+    }
+}
+
+pub const TILE_CODEC_VERSION: u8 = 1;
+
+// bits of each run entry holding the tile code, the rest holds the run length
+const TILE_CODE_BITS: u32 = 4;
+const HEADER_LEN: usize = 9;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CodecError {
+    #[error("Unsupported tile encoding version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Tile encoding is truncated")]
+    Truncated,
+    #[error("Unknown tile code {0}")]
+    UnknownTile(u8),
+    #[error("Tile encoding holds {actual} tiles, expected {expected}")]
+    LengthMismatch { expected: u64, actual: u64 },
+}
+
+impl Tile {
+    fn code(self) -> u8 {
+        match self {
+            Tile::Floor => 0,
+            Tile::Wall => 1,
+            Tile::Void => 2,
+            Tile::Water => 3,
+            Tile::Sand => 4,
+            Tile::Grass => 5,
+            Tile::Forest => 6,
+            Tile::Mountain => 7,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Tile::Floor),
+            1 => Some(Tile::Wall),
+            2 => Some(Tile::Void),
+            3 => Some(Tile::Water),
+            4 => Some(Tile::Sand),
+            5 => Some(Tile::Grass),
+            6 => Some(Tile::Forest),
+            7 => Some(Tile::Mountain),
+            _ => None,
+        }
+    }
+}
+
+// a dense, row-major grid of tiles
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TileGrid {
+    width: u32,
+    height: u32,
+    tiles: Vec<Tile>,
+}
+
+impl TileGrid {
+    #[must_use]
+    pub fn new(size: Size, fill: Tile) -> Self {
+        TileGrid {
+            width: size.width,
+            height: size.height,
+            tiles: vec![fill; size.width as usize * size.height as usize],
+        }
+    }
+
+    #[must_use]
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    #[must_use]
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    #[must_use]
+    pub fn get(&self, point: Point) -> Option<Tile> {
+        if point.x < self.width && point.y < self.height {
+            Some(self.tiles[self.index(point)])
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, point: Point, tile: Tile) {
+        if point.x < self.width && point.y < self.height {
+            let index = self.index(point);
+            self.tiles[index] = tile;
+        }
+    }
+
+    // position of an in-bounds point in `tiles`
+    fn index(&self, point: Point) -> usize {
+        point.y as usize * self.width as usize + point.x as usize
+    }
+
+    // parses a row-major rendered map, unknown characters become `Tile::Void`
+    #[must_use]
+    pub fn from_rendered(rendered: &str) -> Self {
+        let lines: Vec<&str> = rendered.lines().collect();
+        let width = lines.first().map_or(0, |line| line.len()) as u32;
+        let tiles = lines
+            .iter()
+            .flat_map(|line| line.chars())
+            .map(|c| Tile::from_char(c).unwrap_or(Tile::Void))
+            .collect();
+
+        TileGrid {
+            width,
+            height: lines.len() as u32,
+            tiles,
+        }
+    }
+
+    // header of version, width and height (u32 little-endian), followed by runs of equal
+    // tiles, each a LEB128 varint of `(length - 1) << 4 | tile code`
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.tiles.len() / 4);
+        bytes.push(TILE_CODEC_VERSION);
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+
+        let mut tiles = self.tiles.iter().peekable();
+        while let Some(&tile) = tiles.next() {
+            let mut length: u64 = 1;
+            while tiles.next_if_eq(&&tile).is_some() {
+                length += 1;
+            }
+
+            let mut entry = ((length - 1) << TILE_CODE_BITS) | u64::from(tile.code());
+            while entry >= 0x80 {
+                bytes.push((entry as u8) | 0x80);
+                entry >>= 7;
+            }
+            bytes.push(entry as u8);
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, CodecError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(CodecError::Truncated);
+        }
+        if bytes[0] != TILE_CODEC_VERSION {
+            return Err(CodecError::UnsupportedVersion(bytes[0]));
+        }
+        let width = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        let height = u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+        let expected = u64::from(width) * u64::from(height);
+
+        let mut tiles = Vec::new();
+        let mut runs = bytes[HEADER_LEN..].iter();
+        while let Some(&first) = runs.next() {
+            let mut entry = u64::from(first & 0x7f);
+            let mut shift = 7;
+            let mut byte = first;
+            while byte & 0x80 != 0 {
+                byte = *runs.next().ok_or(CodecError::Truncated)?;
+                if shift > 63 {
+                    return Err(CodecError::Truncated);
                 }
+                entry |= u64::from(byte & 0x7f) << shift;
+                shift += 7;
+            }
+
+            let code = (entry & ((1 << TILE_CODE_BITS) - 1)) as u8;
+            let tile = Tile::from_code(code).ok_or(CodecError::UnknownTile(code))?;
+            let length = (entry >> TILE_CODE_BITS) + 1;
+
+            let actual = tiles.len() as u64 + length;
+            if actual > expected {
+                return Err(CodecError::LengthMismatch { expected, actual });
+            }
+            tiles.resize(actual as usize, tile);
+        }
+
+        if tiles.len() as u64 != expected {
+            return Err(CodecError::LengthMismatch {
+                expected,
+                actual: tiles.len() as u64,
+            });
+        }
+        Ok(TileGrid {
+            width,
+            height,
+            tiles,
+        })
+    }
+}
+
+impl fmt::Display for TileGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.tiles.chunks(self.width.max(1) as usize) {
+            for tile in row {
+                write!(f, "{tile}")?;
             }
             writeln!(f)?;
         }
@@ -571,6 +979,22 @@ impl fmt::Display for BSPMap {
     }
 }
 
+// swaps lines and columns of a rendered map, turning one orientation into the other
+#[must_use]
+pub fn transpose(rendered: &str) -> String {
+    let lines: Vec<&[u8]> = rendered.lines().map(str::as_bytes).collect();
+    let chars = lines.first().map_or(0, |line| line.len());
+
+    let mut transposed = String::with_capacity(rendered.len());
+    for char in 0..chars {
+        for line in &lines {
+            transposed.push(line.get(char).copied().unwrap_or(b'x') as char);
+        }
+        transposed.push('\n');
+    }
+    transposed
+}
+
 struct TreeNodeIterator<'a> {
     current_node: Option<&'a TreeNode>,
     right_nodes: Vec<&'a TreeNode>,
@@ -589,10 +1013,10 @@ impl<'a> TreeNodeIterator<'a> {
 
     fn add_left_subtree(&mut self, node: &'a TreeNode) {
         if let Some(ref left) = node.left_child {
-            self.right_nodes.push(&*left);
+            self.right_nodes.push(left);
         }
         if let Some(ref right) = node.right_child {
-            self.right_nodes.push(&*right);
+            self.right_nodes.push(right);
         }
 
         self.current_node = Some(node);
@@ -608,9 +1032,115 @@ impl<'a> Iterator for TreeNodeIterator<'a> {
             self.add_left_subtree(rest);
         }
 
-        match result {
-            Some(node) => Some(&*node),
-            None => None,
-        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::MersenneTwister;
+
+    #[test]
+    fn render_orientation() {
+        let map = BSPMap::new(
+            Size::new(20, 32),
+            MersenneTwister::new(5489),
+            Size::new(6, 6),
+            Size::new(10, 10),
+        )
+        .unwrap();
+
+        let row_major = map.render(Orientation::RowMajor);
+        let rows: Vec<&str> = row_major.lines().collect();
+        assert_eq!(32, rows.len());
+        assert!(rows.iter().all(|row| row.len() == 20));
+        assert_eq!(row_major, format!("{map}"));
+
+        // the outer walls run along the first and last row and column
+        assert!(rows[0].chars().all(|tile| tile == '1'));
+        assert!(rows[31].chars().all(|tile| tile == '1'));
+        assert!(rows
+            .iter()
+            .all(|row| row.starts_with('1') && row.ends_with('1')));
+
+        let column_major = map.render(Orientation::ColumnMajor);
+        assert_eq!(20, column_major.lines().count());
+        assert_eq!(column_major, transpose(&row_major));
+        assert_eq!(row_major, transpose(&column_major));
+    }
+
+    #[test]
+    fn tile_codec_round_trip() {
+        let map = BSPMap::new(
+            Size::new(30, 50),
+            MersenneTwister::new(5489),
+            Size::new(6, 6),
+            Size::new(14, 14),
+        )
+        .unwrap();
+        let grid = map.grid();
+        assert_eq!(format!("{map}"), format!("{grid}"));
+
+        let encoded = grid.encode();
+        assert_eq!(TILE_CODEC_VERSION, encoded[0]);
+        assert!(encoded.len() < 1500 / 3, "{} bytes", encoded.len());
+        assert_eq!(grid, TileGrid::decode(&encoded).unwrap());
+    }
+
+    #[test]
+    fn tile_codec_long_runs() {
+        // a single run longer than one varint byte can hold
+        let grid = TileGrid::new(Size::new(300, 200), Tile::Wall);
+        let encoded = grid.encode();
+        assert_eq!(HEADER_LEN + 3, encoded.len());
+        assert_eq!(grid, TileGrid::decode(&encoded).unwrap());
+
+        let empty = TileGrid::new(Size::new(0, 0), Tile::Void);
+        assert_eq!(empty, TileGrid::decode(&empty.encode()).unwrap());
+    }
+
+    #[test]
+    fn errors_spell_out_limits() {
+        assert_eq!(
+            "Size of a map needs to be less than or equal width : 128, height : 128",
+            MapError::MapTooLarge.to_string()
+        );
+        assert_eq!(
+            "Minimum room size needs to be greater than or equal width : 6, height : 6",
+            MapError::MinRoomTooSmall.to_string()
+        );
+    }
+
+    #[test]
+    fn tile_codec_rejects_malformed_input() {
+        let grid = TileGrid::from_rendered("110\n0x1\n");
+        let encoded = grid.encode();
+        assert_eq!(grid, TileGrid::decode(&encoded).unwrap());
+
+        assert_eq!(Err(CodecError::Truncated), TileGrid::decode(&encoded[..4]));
+
+        let mut version = encoded.clone();
+        version[0] = 9;
+        assert_eq!(
+            Err(CodecError::UnsupportedVersion(9)),
+            TileGrid::decode(&version)
+        );
+
+        let mut unknown = encoded.clone();
+        unknown[HEADER_LEN] = 0x0f;
+        assert_eq!(Err(CodecError::UnknownTile(15)), TileGrid::decode(&unknown));
+
+        assert_eq!(
+            Err(CodecError::LengthMismatch {
+                expected: 6,
+                actual: 5
+            }),
+            TileGrid::decode(&encoded[..encoded.len() - 1])
+        );
+
+        let mut continued = encoded;
+        continued.push(0x80);
+        assert_eq!(Err(CodecError::Truncated), TileGrid::decode(&continued));
     }
 }
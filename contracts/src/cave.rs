@@ -0,0 +1,265 @@
+use crate::bsp::{Point, Size, Tile, TileGrid, MAX_MAP_SIZE};
+use crate::rand::MapRng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+pub struct CaveParams {
+    pub size: Size,
+    // chance in percent that a cell starts out as wall
+    pub fill_percent: u32,
+    // number of smoothing passes
+    pub iterations: u32,
+    // a floor cell turns into wall with at least this many wall neighbours
+    pub birth_limit: u32,
+    // a wall cell stays wall with at least this many wall neighbours
+    pub survival_limit: u32,
+}
+
+pub const MIN_CAVE_SIZE: Size = Size::new(3, 3);
+pub const MAX_CAVE_ITERATIONS: u32 = 10;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CaveError {
+    #[error(
+        "Size of a cave map needs to be greater than or equal {}",
+        MIN_CAVE_SIZE
+    )]
+    TooSmall,
+    #[error("Size of a cave map needs to be less than or equal {}", MAX_MAP_SIZE)]
+    TooLarge,
+    #[error("Fill percentage must be at most 100.")]
+    FillPercentTooLarge,
+    #[error(
+        "Number of smoothing iterations must be at most {}.",
+        MAX_CAVE_ITERATIONS
+    )]
+    TooManyIterations,
+    #[error("Birth and survival limits must be at most 8.")]
+    NeighbourLimitTooLarge,
+}
+
+impl CaveParams {
+    pub fn validate(&self) -> Result<(), CaveError> {
+        if !MIN_CAVE_SIZE.fits_within(self.size) {
+            return Err(CaveError::TooSmall);
+        }
+        if !self.size.fits_within(MAX_MAP_SIZE) {
+            return Err(CaveError::TooLarge);
+        }
+        if self.fill_percent > 100 {
+            return Err(CaveError::FillPercentTooLarge);
+        }
+        if self.iterations > MAX_CAVE_ITERATIONS {
+            return Err(CaveError::TooManyIterations);
+        }
+        if self.birth_limit > 8 || self.survival_limit > 8 {
+            return Err(CaveError::NeighbourLimitTooLarge);
+        }
+        Ok(())
+    }
+}
+
+pub fn from_params(params: &CaveParams, mut seed: impl MapRng) -> Result<TileGrid, CaveError> {
+    params.validate()?;
+
+    let mut grid = random_fill(params, &mut seed);
+    for _ in 0..params.iterations {
+        grid = smooth(&grid, params.birth_limit, params.survival_limit);
+    }
+    keep_largest_region(&mut grid);
+
+    Ok(grid)
+}
+
+// walls all around the border, the inside filled at random
+fn random_fill(params: &CaveParams, rng: &mut impl MapRng) -> TileGrid {
+    let size = params.size;
+    let mut grid = TileGrid::new(size, Tile::Wall);
+    for y in 1..size.height() - 1 {
+        for x in 1..size.width() - 1 {
+            if rng.range(0, 99) >= params.fill_percent {
+                grid.set(Point::new(x, y), Tile::Floor);
+            }
+        }
+    }
+    grid
+}
+
+// one cellular-automata step; the border always stays wall
+fn smooth(grid: &TileGrid, birth_limit: u32, survival_limit: u32) -> TileGrid {
+    let size = grid.size();
+    let mut next = TileGrid::new(size, Tile::Wall);
+    for y in 1..size.height() - 1 {
+        for x in 1..size.width() - 1 {
+            let point = Point::new(x, y);
+            let walls = wall_neighbours(grid, point);
+            let wall = match grid.get(point) {
+                Some(Tile::Wall) => walls >= survival_limit,
+                _ => walls >= birth_limit,
+            };
+            if !wall {
+                next.set(point, Tile::Floor);
+            }
+        }
+    }
+    next
+}
+
+// walls among the 8 surrounding cells, cells outside the grid count as wall
+fn wall_neighbours(grid: &TileGrid, point: Point) -> u32 {
+    let mut walls = 0;
+    for dy in 0..3 {
+        for dx in 0..3 {
+            if dx == 1 && dy == 1 {
+                continue;
+            }
+            let neighbour = match (
+                (point.x() + dx).checked_sub(1),
+                (point.y() + dy).checked_sub(1),
+            ) {
+                (Some(x), Some(y)) => grid.get(Point::new(x, y)),
+                _ => None,
+            };
+            if neighbour != Some(Tile::Floor) {
+                walls += 1;
+            }
+        }
+    }
+    walls
+}
+
+// fills in every floor region but the largest, so the whole cave is reachable
+pub(crate) fn keep_largest_region(grid: &mut TileGrid) {
+    let size = grid.size();
+    let index = |point: Point| (point.y() * size.width() + point.x()) as usize;
+
+    let mut region_of = vec![None; grid.tiles().len()];
+    let mut region_sizes: Vec<usize> = vec![];
+    for y in 0..size.height() {
+        for x in 0..size.width() {
+            let start = Point::new(x, y);
+            if grid.get(start) != Some(Tile::Floor) || region_of[index(start)].is_some() {
+                continue;
+            }
+
+            let region = region_sizes.len();
+            let mut cells = 0;
+            let mut stack = vec![start];
+            region_of[index(start)] = Some(region);
+            while let Some(point) = stack.pop() {
+                cells += 1;
+                let neighbours = [
+                    point.x().checked_sub(1).map(|x| Point::new(x, point.y())),
+                    Some(Point::new(point.x() + 1, point.y())),
+                    point.y().checked_sub(1).map(|y| Point::new(point.x(), y)),
+                    Some(Point::new(point.x(), point.y() + 1)),
+                ];
+                for neighbour in neighbours.into_iter().flatten() {
+                    if grid.get(neighbour) == Some(Tile::Floor)
+                        && region_of[index(neighbour)].is_none()
+                    {
+                        region_of[index(neighbour)] = Some(region);
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            region_sizes.push(cells);
+        }
+    }
+
+    // the first of the largest regions wins a tie
+    let largest = region_sizes
+        .iter()
+        .enumerate()
+        .fold(
+            None,
+            |best: Option<(usize, usize)>, (region, cells)| match best {
+                Some((_, best_cells)) if best_cells >= *cells => best,
+                _ => Some((region, *cells)),
+            },
+        )
+        .map(|(region, _)| region);
+    for y in 0..size.height() {
+        for x in 0..size.width() {
+            let point = Point::new(x, y);
+            if region_of[index(point)].is_some() && region_of[index(point)] != largest {
+                grid.set(point, Tile::Wall);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::MersenneTwister;
+
+    fn params() -> CaveParams {
+        CaveParams {
+            size: Size::new(40, 30),
+            fill_percent: 45,
+            iterations: 4,
+            birth_limit: 5,
+            survival_limit: 4,
+        }
+    }
+
+    #[test]
+    fn cave_is_enclosed_and_connected() {
+        let grid = from_params(&params(), MersenneTwister::new(7)).unwrap();
+        let size = grid.size();
+
+        for x in 0..size.width() {
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(x, 0)));
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(x, size.height() - 1)));
+        }
+        for y in 0..size.height() {
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(0, y)));
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(size.width() - 1, y)));
+        }
+
+        // a second pass finds nothing left to fill in
+        let floors = grid
+            .tiles()
+            .iter()
+            .filter(|tile| **tile == Tile::Floor)
+            .count();
+        assert!(floors > 0);
+        let mut connected = grid.clone();
+        keep_largest_region(&mut connected);
+        assert_eq!(grid, connected);
+    }
+
+    #[test]
+    fn keep_largest_region_fills_smaller_regions() {
+        let mut grid = TileGrid::from_rendered("11111\n10101\n10111\n11111\n");
+        keep_largest_region(&mut grid);
+        assert_eq!("11111\n10111\n10111\n11111\n", grid.to_string());
+    }
+
+    #[test]
+    fn cave_params_validation() {
+        let invalid = [
+            (Size::new(2, 30), 45, 4, CaveError::TooSmall),
+            (Size::new(40, 30), 101, 4, CaveError::FillPercentTooLarge),
+            (Size::new(40, 30), 45, 11, CaveError::TooManyIterations),
+        ];
+        for (size, fill_percent, iterations, err) in invalid {
+            let params = CaveParams {
+                size,
+                fill_percent,
+                iterations,
+                ..params()
+            };
+            assert_eq!(Err(err), params.validate());
+        }
+
+        let params = CaveParams {
+            birth_limit: 9,
+            ..params()
+        };
+        assert_eq!(Err(CaveError::NeighbourLimitTooLarge), params.validate());
+    }
+}
@@ -1,46 +1,411 @@
 use cosmwasm_std::{
-    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    entry_point, to_binary, Addr, BankMsg, Binary, BlockInfo, Coin, CosmosMsg, Deps, DepsMut, Env,
+    MessageInfo, Response, StdError, StdResult, Storage, Uint128, WasmMsg,
 };
+use secret_toolkit_permit::{validate, Permit, RevokedPermits, TokenPermissions};
+use secret_toolkit_viewing_key::{ViewingKey, ViewingKeyStore};
 
-use crate::bsp::{BSPMap, Size};
+use crate::bsp::{MapParams, Size};
+use crate::cave::CaveParams;
+use crate::error::ContractError;
+use crate::generator::GeneratorParams;
 use crate::msg::{
-    CountResponse, ExecuteMsg, InstantiateMsg, MapCountResponse, MapResponse, MapsResponse,
-    QueryMsg,
+    CompactMapResponse, ConfigResponse, CountResponse, ExecuteAnswer, ExecuteMsg, FeeResponse,
+    GeneratorKind, InstantiateMsg, LimitsResponse, MapCountResponse, MapDataResponse, MapEncoding,
+    MapInfoResponse, MapMetadata, MapResponse, MapsResponse, MigrateMsg, Order, QueryAnswer,
+    QueryMsg, QueryWithPermit, QuotaResponse, ReceiverMsg, ResponseStatus, Send, TilesetResponse,
+    Transfer, ViewerInfo,
 };
-use crate::rand::MersenneTwister;
-use crate::state::{config, config_read, State};
+use crate::noise::NoiseParams;
+use crate::rand::GeneratorVersion;
+use crate::state::{
+    add_collected_fee, add_owner_map, add_owner_token, can_view, config, config_read, is_approved,
+    legacy_config_read, owner_map_count, owner_maps_page, owner_tokens, owner_tokens_page,
+    remove_owner_map, remove_owner_token, set_approval, store_tileset, Approval, ContractVersion,
+    Expiration, Limits, MapInfo, Receiver, State, StorageMode, StoredMap, COLLECTED_FEES,
+    CONTRACT_VERSION, LAST_GENERATED, LIVE_MAP_COUNT, MAPS, MAP_COUNT, MAP_INFOS, OPERATORS,
+    PERMITS_PREFIX, RECEIVERS, TILESETS, TILESET_CONTENTS, TOKEN_APPROVALS, TOKEN_COUNT, VIEWERS,
+};
+use crate::walk::WalkParams;
+use crate::wfc::{Tileset, WfcError, WfcParams};
+
+pub const DEFAULT_MAP_SIZE: Size = Size::new(30, 50);
+pub const DEFAULT_MIN_ROOM_SIZE: Size = Size::new(6, 6);
+pub const DEFAULT_MAX_ROOM_SIZE: Size = Size::new(14, 14);
+
+pub const DEFAULT_CAVE_FILL_PERCENT: u32 = 45;
+pub const DEFAULT_CAVE_ITERATIONS: u32 = 4;
+pub const DEFAULT_CAVE_BIRTH_LIMIT: u32 = 5;
+pub const DEFAULT_CAVE_SURVIVAL_LIMIT: u32 = 4;
+
+pub const DEFAULT_WALK_FLOOR_PERCENT: u32 = 40;
+pub const DEFAULT_WALKERS: u32 = 4;
+
+pub const DEFAULT_NOISE_OCTAVES: u32 = 4;
+pub const DEFAULT_NOISE_SCALE: u32 = 16;
+pub const DEFAULT_SEA_LEVEL: u32 = 25;
+pub const DEFAULT_BEACH_LEVEL: u32 = 35;
+pub const DEFAULT_MOUNTAIN_LEVEL: u32 = 80;
+pub const DEFAULT_FOREST_MOISTURE: u32 = 50;
+
+pub const DEFAULT_PAGE_LIMIT: u32 = 10;
+pub const MAX_PAGE_LIMIT: u32 = 30;
+// ids looked at for a page of `GetMaps`, private and deleted maps included
+pub const MAX_PAGE_SCAN: u32 = 100;
+
+// maps stored as a seed are rebuilt on every query, so they are kept small
+// enough for a full page of them to render within query gas
+pub const MAX_SEED_MAP_CELLS: u64 = 64 * 64;
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION_STR: &str = env!("CARGO_PKG_VERSION");
+
+pub const TOKEN_NAME: &str = "Map Gen";
+pub const TOKEN_SYMBOL: &str = "MAP";
 
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     let state = State {
         count: msg.count,
         owner: info.sender.clone(),
-        maps: vec![],
+        storage_mode: msg.storage_mode.unwrap_or_default(),
+        pending_owner: None,
+        admins: vec![],
+        paused: false,
+        fee: msg.fee,
+        limits: msg.limits.unwrap_or_default(),
+        cleared_below: 0,
+        map_epoch: 0,
     };
 
     deps.api
         .debug(format!("Contract was initialized by {}", info.sender).as_str());
     config(deps.storage).save(&state)?;
+    MAP_COUNT.save(deps.storage, &0)?;
+    LIVE_MAP_COUNT.save(deps.storage, &0)?;
+    TOKEN_COUNT.save(deps.storage, &0)?;
+    ViewingKey::set_seed(deps.storage, block_random(&env)?.as_slice());
+    CONTRACT_VERSION.save(deps.storage, &current_version())?;
 
     Ok(Response::default())
 }
 
 #[entry_point]
-pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    match CONTRACT_VERSION.may_load(deps.storage)? {
+        Some(stored) => {
+            if stored.contract != CONTRACT_NAME || parse_version(&stored.version).is_none() {
+                return Err(ContractError::UnsupportedMigration {
+                    contract: stored.contract,
+                    version: stored.version,
+                });
+            }
+            // refuse downgrades, the newer layout may not be readable here
+            if parse_version(&stored.version) > parse_version(CONTRACT_VERSION_STR) {
+                return Err(ContractError::UnsupportedMigration {
+                    contract: stored.contract,
+                    version: stored.version,
+                });
+            }
+        }
+        None => migrate_legacy_state(deps.storage, &env)?,
+    }
+    if TOKEN_COUNT.may_load(deps.storage)?.is_none() {
+        index_tokens(deps.storage)?;
+    }
+    if LIVE_MAP_COUNT.may_load(deps.storage)?.is_none() {
+        count_live_maps(deps.storage)?;
+    }
+    CONTRACT_VERSION.save(deps.storage, &current_version())?;
+
+    deps.api.debug("contract migrated successfully");
+    Ok(Response::new().add_attribute("version", CONTRACT_VERSION_STR))
+}
+
+// the block's Secret VRF output, which seeds both maps and viewing keys
+fn block_random(env: &Env) -> Result<Binary, ContractError> {
+    env.block
+        .random
+        .clone()
+        .filter(|random| !random.is_empty())
+        .ok_or(ContractError::MissingRandomness {})
+}
+
+fn current_version() -> ContractVersion {
+    ContractVersion {
+        contract: CONTRACT_NAME.to_string(),
+        version: CONTRACT_VERSION_STR.to_string(),
+    }
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+// builds the token index for maps minted before it existed
+fn index_tokens(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    let mut token_count = 0;
+    for id in 0..MAP_COUNT.load(storage)? {
+        if let Some(map_info) = MAP_INFOS.get(storage, &id).filter(|info| info.minted) {
+            owner_tokens(&map_info.owner).push(storage, &id)?;
+            token_count += 1;
+        }
+    }
+    TOKEN_COUNT.save(storage, &token_count)?;
+    Ok(())
+}
+
+// counts the maps stored before the count was kept, which `Clear` had not yet left
+// behind in storage
+fn count_live_maps(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    let live = (0..MAP_COUNT.load(storage)?)
+        .filter(|id| MAPS.contains(storage, id))
+        .count();
+    LIVE_MAP_COUNT.save(storage, &(live as u32))?;
+    Ok(())
+}
+
+// moves the maps out of `LegacyState` into the keymap as column-major `StoredMap::V1`
+fn migrate_legacy_state(storage: &mut dyn Storage, env: &Env) -> Result<(), ContractError> {
+    let legacy = legacy_config_read(storage).load()?;
+
+    let map_count = legacy.maps.len() as u32;
+    for (id, map) in (0..map_count).zip(legacy.maps) {
+        MAPS.insert(storage, &id, &StoredMap::V1(map))?;
+    }
+    MAP_COUNT.save(storage, &map_count)?;
+    config(storage).save(&State {
+        count: legacy.count,
+        owner: legacy.owner,
+        storage_mode: StorageMode::default(),
+        pending_owner: None,
+        admins: vec![],
+        paused: false,
+        fee: None,
+        limits: Limits::default(),
+        cleared_below: 0,
+        map_epoch: 0,
+    })?;
+    ViewingKey::set_seed(storage, block_random(env)?.as_slice());
+
+    Ok(())
+}
+
+#[entry_point]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    // while paused only administration, the admins' own clean-up and key management go through
+    let allowed_while_paused = matches!(
+        msg,
+        ExecuteMsg::Reset { .. }
+            | ExecuteMsg::Clear {}
+            | ExecuteMsg::CreateViewingKey { .. }
+            | ExecuteMsg::SetViewingKey { .. }
+            | ExecuteMsg::RevokePermit { .. }
+            | ExecuteMsg::TransferOwnership { .. }
+            | ExecuteMsg::AcceptOwnership {}
+            | ExecuteMsg::AddAdmin { .. }
+            | ExecuteMsg::RemoveAdmin { .. }
+            | ExecuteMsg::SetPaused { .. }
+            | ExecuteMsg::SetFee { .. }
+            | ExecuteMsg::SetLimits { .. }
+            | ExecuteMsg::WithdrawFees { .. }
+            | ExecuteMsg::SetTileset { .. }
+            | ExecuteMsg::RemoveTileset { .. }
+            | ExecuteMsg::Revoke { .. }
+            | ExecuteMsg::RevokeAll { .. }
+    );
+    if !allowed_while_paused && config_read(deps.storage).load()?.paused {
+        return Err(ContractError::Paused {});
+    }
+
     match msg {
         ExecuteMsg::Increment {} => try_increment(deps, env),
         ExecuteMsg::Reset { count } => try_reset(deps, info, count),
-        ExecuteMsg::Generate {} => try_generate(deps, env),
-        ExecuteMsg::Clear {} => try_clear(deps, env),
+        ExecuteMsg::Generate {
+            size,
+            min_room_size,
+            max_room_size,
+            private,
+            mint,
+            generator,
+        } => {
+            let params = generator_params(
+                deps.storage,
+                size.unwrap_or(DEFAULT_MAP_SIZE),
+                min_room_size,
+                max_room_size,
+                generator.unwrap_or(GeneratorKind::Bsp {}),
+            )?;
+            try_generate(
+                deps,
+                env,
+                info,
+                params,
+                // tokens keep their tiles in private metadata unless asked otherwise
+                private.unwrap_or(mint.unwrap_or(false)),
+                mint.unwrap_or(false),
+            )
+        }
+        ExecuteMsg::Clear {} => try_clear(deps, env, info),
+        ExecuteMsg::DeleteMap { id } => try_delete_map(deps, env, info, id),
+        ExecuteMsg::CreateViewingKey { entropy } => {
+            try_create_viewing_key(deps, env, info, entropy)
+        }
+        ExecuteMsg::SetViewingKey { key } => try_set_viewing_key(deps, env, info, key),
+        ExecuteMsg::RevokePermit { permit_name } => try_revoke_permit(deps, env, info, permit_name),
+        ExecuteMsg::AddViewer { viewer } => try_set_viewer(deps, env, info, viewer, true),
+        ExecuteMsg::RemoveViewer { viewer } => try_set_viewer(deps, env, info, viewer, false),
+        ExecuteMsg::TransferOwnership { new_owner } => {
+            try_transfer_ownership(deps, env, info, new_owner)
+        }
+        ExecuteMsg::AcceptOwnership {} => try_accept_ownership(deps, env, info),
+        ExecuteMsg::AddAdmin { admin } => try_set_admin(deps, env, info, admin, true),
+        ExecuteMsg::RemoveAdmin { admin } => try_set_admin(deps, env, info, admin, false),
+        ExecuteMsg::SetPaused { paused } => try_set_paused(deps, env, info, paused),
+        ExecuteMsg::SetFee { fee } => try_set_fee(deps, env, info, fee),
+        ExecuteMsg::SetLimits { limits } => try_set_limits(deps, env, info, limits),
+        ExecuteMsg::WithdrawFees { recipient } => try_withdraw_fees(deps, env, info, recipient),
+        ExecuteMsg::SetTileset { name, tileset } => {
+            try_set_tileset(deps, env, info, name, Some(tileset))
+        }
+        ExecuteMsg::RemoveTileset { name } => try_set_tileset(deps, env, info, name, None),
+        ExecuteMsg::TransferNft {
+            recipient,
+            token_id,
+            memo,
+        } => {
+            let transfers = vec![Transfer {
+                recipient,
+                token_ids: vec![token_id],
+                memo,
+            }];
+            try_transfer_nft(deps, env, info, transfers, false)
+        }
+        ExecuteMsg::BatchTransferNft { transfers } => {
+            try_transfer_nft(deps, env, info, transfers, true)
+        }
+        ExecuteMsg::SendNft {
+            contract,
+            receiver_info,
+            token_id,
+            msg,
+            memo,
+        } => {
+            let sends = vec![Send {
+                contract,
+                receiver_info,
+                token_ids: vec![token_id],
+                msg,
+                memo,
+            }];
+            try_send_nft(deps, env, info, sends, false)
+        }
+        ExecuteMsg::BatchSendNft { sends } => try_send_nft(deps, env, info, sends, true),
+        ExecuteMsg::Approve {
+            spender,
+            token_id,
+            expires,
+        } => {
+            let expires = expires.unwrap_or(Expiration::Never);
+            try_set_approval(deps, env, info, spender, token_id, Some(expires))
+        }
+        ExecuteMsg::Revoke { spender, token_id } => {
+            try_set_approval(deps, env, info, spender, token_id, None)
+        }
+        ExecuteMsg::ApproveAll { operator, expires } => {
+            let expires = expires.unwrap_or(Expiration::Never);
+            try_set_operator(deps, env, info, operator, Some(expires))
+        }
+        ExecuteMsg::RevokeAll { operator } => try_set_operator(deps, env, info, operator, None),
+        ExecuteMsg::RegisterReceiveNft {
+            code_hash,
+            also_implements_batch_receive_nft,
+        } => try_register_receive_nft(
+            deps,
+            env,
+            info,
+            code_hash,
+            also_implements_batch_receive_nft.unwrap_or(false),
+        ),
     }
 }
 
-pub fn try_increment(deps: DepsMut, _env: Env) -> StdResult<Response> {
+// SNIP-721 answers are returned as the response data
+fn answer(answer: ExecuteAnswer) -> Result<Response, ContractError> {
+    Ok(Response::new().set_data(to_binary(&answer)?))
+}
+
+fn generator_params(
+    storage: &dyn Storage,
+    size: Size,
+    min_room_size: Option<Size>,
+    max_room_size: Option<Size>,
+    generator: GeneratorKind,
+) -> Result<GeneratorParams, ContractError> {
+    Ok(match generator {
+        GeneratorKind::Bsp {} => GeneratorParams::Bsp(MapParams {
+            size,
+            min_room_size: min_room_size.unwrap_or(DEFAULT_MIN_ROOM_SIZE),
+            max_room_size: max_room_size.unwrap_or(DEFAULT_MAX_ROOM_SIZE),
+        }),
+        GeneratorKind::Cave {
+            fill_percent,
+            iterations,
+            birth_limit,
+            survival_limit,
+        } => GeneratorParams::Cave(CaveParams {
+            size,
+            fill_percent: fill_percent.unwrap_or(DEFAULT_CAVE_FILL_PERCENT),
+            iterations: iterations.unwrap_or(DEFAULT_CAVE_ITERATIONS),
+            birth_limit: birth_limit.unwrap_or(DEFAULT_CAVE_BIRTH_LIMIT),
+            survival_limit: survival_limit.unwrap_or(DEFAULT_CAVE_SURVIVAL_LIMIT),
+        }),
+        GeneratorKind::Walk {
+            floor_percent,
+            walkers,
+            bias,
+        } => GeneratorParams::Walk(WalkParams {
+            size,
+            floor_percent: floor_percent.unwrap_or(DEFAULT_WALK_FLOOR_PERCENT),
+            walkers: walkers.unwrap_or(DEFAULT_WALKERS),
+            bias,
+        }),
+        GeneratorKind::Wfc { tileset } => GeneratorParams::Wfc(WfcParams {
+            size,
+            tileset: TILESETS
+                .get(storage, &tileset)
+                .ok_or(ContractError::TilesetNotFound { name: tileset })?,
+        }),
+        GeneratorKind::Noise {
+            octaves,
+            scale,
+            sea_level,
+            beach_level,
+            mountain_level,
+            forest_moisture,
+        } => GeneratorParams::Noise(NoiseParams {
+            size,
+            octaves: octaves.unwrap_or(DEFAULT_NOISE_OCTAVES),
+            scale: scale.unwrap_or(DEFAULT_NOISE_SCALE),
+            sea_level: sea_level.unwrap_or(DEFAULT_SEA_LEVEL),
+            beach_level: beach_level.unwrap_or(DEFAULT_BEACH_LEVEL),
+            mountain_level: mountain_level.unwrap_or(DEFAULT_MOUNTAIN_LEVEL),
+            forest_moisture: forest_moisture.unwrap_or(DEFAULT_FOREST_MOISTURE),
+        }),
+    })
+}
+
+pub fn try_increment(deps: DepsMut, _env: Env) -> Result<Response, ContractError> {
     config(deps.storage).update(|mut state| -> Result<_, StdError> {
         state.count += 1;
         Ok(state)
@@ -50,11 +415,11 @@ pub fn try_increment(deps: DepsMut, _env: Env) -> StdResult<Response> {
     Ok(Response::default())
 }
 
-pub fn try_reset(deps: DepsMut, info: MessageInfo, count: i32) -> StdResult<Response> {
+pub fn try_reset(deps: DepsMut, info: MessageInfo, count: i32) -> Result<Response, ContractError> {
     let sender_address = info.sender.clone();
     config(deps.storage).update(|mut state| {
-        if sender_address != state.owner {
-            return Err(StdError::generic_err("Only the owner can reset count"));
+        if !state.is_admin(&sender_address) {
+            return Err(ContractError::Unauthorized {});
         }
         state.count = count;
         Ok(state)
@@ -64,190 +429,4311 @@ pub fn try_reset(deps: DepsMut, info: MessageInfo, count: i32) -> StdResult<Resp
     Ok(Response::default())
 }
 
-pub fn try_generate(deps: DepsMut, env: Env) -> StdResult<Response> {
-    let random_binary = env.block.random.clone();
-    let random_bytes = &random_binary.as_ref().unwrap().0;
+pub fn try_generate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    params: GeneratorParams,
+    private: bool,
+    mint: bool,
+) -> Result<Response, ContractError> {
+    let random = block_random(&env)?;
 
-    let random_number = u32::from_le_bytes([
-        random_bytes[0],
-        random_bytes[1],
-        random_bytes[2],
-        random_bytes[3],
-    ]);
+    let state = config_read(deps.storage).load()?;
+    let refund = charge_fee(state.fee.as_ref(), &info.funds)?;
+    if let Some(fee) = state.fee.as_ref().filter(|fee| !fee.amount.is_zero()) {
+        add_collected_fee(deps.storage, fee)?;
+    }
 
-    let map = BSPMap::new(
-        Size::new(30, 50),
-        MersenneTwister::new(random_number),
-        Size::new(6, 6),
-        Size::new(14, 14),
-    )
-    .unwrap();
+    let quota = quota(deps.as_ref(), &state.limits, &info.sender, env.block.height)?;
+    if quota.remaining_maps == Some(0) {
+        return Err(ContractError::MapQuotaExceeded {
+            max: state.limits.max_maps_per_owner.unwrap_or_default(),
+        });
+    }
+    if env.block.height < quota.next_height {
+        return Err(ContractError::GenerateTooSoon {
+            next_height: quota.next_height,
+        });
+    }
+    LAST_GENERATED.insert(deps.storage, &info.sender, &env.block.height)?;
 
-    config(deps.storage).update(|mut state| -> Result<_, StdError> {
-        state.maps.push(format!("{map}"));
-        Ok(state)
-    })?;
+    let size = params.size();
+    if state.storage_mode == StorageMode::Seed
+        && u64::from(size.width()) * u64::from(size.height()) > MAX_SEED_MAP_CELLS
+    {
+        return Err(ContractError::SeedMapTooLarge {
+            max_cells: MAX_SEED_MAP_CELLS,
+        });
+    }
+
+    let stored = match (state.storage_mode, params) {
+        (StorageMode::Tiles, params) => {
+            let map = params.generate(
+                deps.storage,
+                GeneratorVersion::CURRENT.rng(random.as_slice()),
+            )?;
+            StoredMap::V4 {
+                tiles: Binary::from(map.grid.encode()),
+                rooms: map.rooms,
+                corridors: map.corridors,
+                seed: random,
+                generator_version: GeneratorVersion::CURRENT,
+            }
+        }
+        (StorageMode::Seed, params) => {
+            // wave function collapse and random walks can fail on valid
+            // parameters, so the map is built once up front to be sure it replays
+            params.generate(
+                deps.storage,
+                GeneratorVersion::CURRENT.rng(random.as_slice()),
+            )?;
+            StoredMap::V5 {
+                seed: random,
+                generator_version: GeneratorVersion::CURRENT,
+                params,
+            }
+        }
+    };
+
+    let id = MAP_COUNT.load(deps.storage)?;
+    MAPS.insert(deps.storage, &id, &stored)?;
+    MAP_INFOS.insert(
+        deps.storage,
+        &id,
+        &MapInfo {
+            creator: info.sender.clone(),
+            owner: info.sender.clone(),
+            height: env.block.height,
+            time: env.block.time,
+            private,
+            minted: mint,
+        },
+    )?;
+    add_owner_map(deps.storage, &info.sender, id)?;
+    if mint {
+        add_owner_token(deps.storage, &info.sender, id)?;
+        TOKEN_COUNT.update(deps.storage, |count| Ok::<_, StdError>(count + 1))?;
+    }
+    MAP_COUNT.save(deps.storage, &(id + 1))?;
+    LIVE_MAP_COUNT.update(deps.storage, |count| Ok::<_, StdError>(count + 1))?;
 
     deps.api.debug("map generated successfully");
-    Ok(Response::default())
+    let mut response = Response::new().add_attribute("map_id", id.to_string());
+    if mint {
+        response = response.add_attribute("token_id", id.to_string());
+    }
+    if !refund.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.into_string(),
+            amount: refund,
+        });
+    }
+    Ok(response)
 }
 
-pub fn try_clear(deps: DepsMut, _env: Env) -> StdResult<Response> {
-    config(deps.storage).update(|mut state| -> Result<_, StdError> {
+fn quota(deps: Deps, limits: &Limits, address: &Addr, height: u64) -> StdResult<QuotaResponse> {
+    let maps = owner_map_count(deps.storage, address)?;
+    let next_height = match (
+        LAST_GENERATED.get(deps.storage, address),
+        limits.min_blocks_between_generations,
+    ) {
+        (Some(last), Some(min_blocks)) => last.saturating_add(min_blocks),
+        _ => height,
+    };
+
+    Ok(QuotaResponse {
+        maps,
+        remaining_maps: limits
+            .max_maps_per_owner
+            .map(|max| max.saturating_sub(maps)),
+        next_height: next_height.max(height),
+    })
+}
+
+// checks `funds` covers the fee and returns what was paid on top of it
+fn charge_fee(fee: Option<&Coin>, funds: &[Coin]) -> Result<Vec<Coin>, ContractError> {
+    let mut refund: Vec<Coin> = funds.to_vec();
+    if let Some(fee) = fee {
+        let paid = funds
+            .iter()
+            .filter(|coin| coin.denom == fee.denom)
+            .fold(Uint128::zero(), |paid, coin| paid + coin.amount);
+        if paid < fee.amount {
+            return Err(ContractError::InsufficientFee {
+                required: fee.clone(),
+                paid,
+            });
+        }
+
+        let mut remaining = fee.amount;
+        for coin in refund.iter_mut().filter(|coin| coin.denom == fee.denom) {
+            let taken = coin.amount.min(remaining);
+            coin.amount -= taken;
+            remaining -= taken;
+        }
+    }
+    refund.retain(|coin| !coin.amount.is_zero());
+    Ok(refund)
+}
+
+// removes every map that was not minted; tokens belong to their holders and are
+// kept, and ids are never handed out again
+pub fn try_clear(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let map_count = MAP_COUNT.load(deps.storage)?;
+    config(deps.storage).update(|mut state| {
+        if !state.is_admin(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
         state.count = 0;
-        state.maps = vec![];
+        state.cleared_below = map_count;
+        state.map_epoch += 1;
         Ok(state)
     })?;
 
-    deps.api.debug("count incremented successfully");
+    // every map left is a token
+    let token_count = TOKEN_COUNT.load(deps.storage)?;
+    LIVE_MAP_COUNT.save(deps.storage, &token_count)?;
+
+    deps.api.debug("maps cleared successfully");
     Ok(Response::default())
 }
 
-#[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetCount {} => to_binary(&query_count(deps)?),
-        QueryMsg::GetMaps {} => to_binary(&query_maps(deps)?),
-        QueryMsg::GetMap { index } => to_binary(&query_map(deps, index)?),
-        QueryMsg::GetMapCount {} => to_binary(&query_map_count(deps)?),
+pub fn try_delete_map(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    id: u32,
+) -> Result<Response, ContractError> {
+    let state = config_read(deps.storage).load()?;
+    if !MAPS.contains(deps.storage, &id) || state.is_cleared(deps.storage, id) {
+        return Err(ContractError::MapNotFound { index: id });
     }
-}
+    // maps stored before creators were recorded have no one who may delete them,
+    // and maps that were transferred away can only be deleted by their new owner
+    let map_info = match MAP_INFOS.get(deps.storage, &id) {
+        Some(map_info) if map_info.owner == info.sender => map_info,
+        _ => return Err(ContractError::Unauthorized {}),
+    };
 
-fn query_count(deps: Deps) -> StdResult<CountResponse> {
-    let state = config_read(deps.storage).load()?;
-    Ok(CountResponse { count: state.count })
-}
+    MAPS.remove(deps.storage, &id)?;
+    MAP_INFOS.remove(deps.storage, &id)?;
+    remove_owner_map(deps.storage, &info.sender, id)?;
+    if map_info.minted {
+        remove_owner_token(deps.storage, &info.sender, id)?;
+        TOKEN_APPROVALS.remove(deps.storage, &id)?;
+        TOKEN_COUNT.update(deps.storage, |count| Ok::<_, StdError>(count - 1))?;
+    }
+    LIVE_MAP_COUNT.update(deps.storage, |count| Ok::<_, StdError>(count - 1))?;
 
-fn query_maps(deps: Deps) -> StdResult<MapsResponse> {
-    let state = config_read(deps.storage).load()?;
-    Ok(MapsResponse { maps: state.maps })
+    deps.api.debug("map deleted successfully");
+    Ok(Response::new().add_attribute("map_id", id.to_string()))
 }
 
-fn query_map(deps: Deps, index: u32) -> StdResult<MapResponse> {
-    let state = config_read(deps.storage).load()?;
-    Ok(MapResponse {
-        index,
-        map: state.maps[index as usize].clone(),
-    })
-}
+pub fn try_create_viewing_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    entropy: String,
+) -> Result<Response, ContractError> {
+    let key = ViewingKey::create(
+        deps.storage,
+        &info,
+        &env,
+        info.sender.as_str(),
+        entropy.as_bytes(),
+    );
 
-fn query_map_count(deps: Deps) -> StdResult<MapCountResponse> {
-    let state = config_read(deps.storage).load()?;
-    Ok(MapCountResponse {
-        count: state.maps.len(),
-    })
+    answer(ExecuteAnswer::ViewingKey { key })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::*;
-    use cosmwasm_std::{from_binary, Coin, StdError, Uint128};
+pub fn try_set_viewing_key(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    key: String,
+) -> Result<Response, ContractError> {
+    ViewingKey::set(deps.storage, info.sender.as_str(), &key);
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies();
-        let info = mock_info(
-            "creator",
-            &[Coin {
-                denom: "earth".to_string(),
-                amount: Uint128::new(1000),
-            }],
-        );
-        let init_msg = InstantiateMsg { count: 17 };
+    answer(ExecuteAnswer::ViewingKey { key })
+}
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+pub fn try_revoke_permit(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    permit_name: String,
+) -> Result<Response, ContractError> {
+    RevokedPermits::revoke_permit(
+        deps.storage,
+        PERMITS_PREFIX,
+        info.sender.as_str(),
+        &permit_name,
+    );
 
-        assert_eq!(0, res.messages.len());
+    Ok(Response::default())
+}
 
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: CountResponse = from_binary(&res).unwrap();
-        assert_eq!(17, value.count);
+pub fn try_set_viewer(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    viewer: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    let viewer = deps.api.addr_validate(&viewer)?;
+    let key = (info.sender, viewer);
+    if allowed {
+        VIEWERS.insert(deps.storage, &key, &true)?;
+    } else {
+        VIEWERS.remove(deps.storage, &key)?;
     }
 
-    #[test]
-    fn increment() {
-        let mut deps = mock_dependencies_with_balance(&[Coin {
-            denom: "token".to_string(),
-            amount: Uint128::new(2),
-        }]);
-        let info = mock_info(
-            "creator",
-            &[Coin {
-                denom: "token".to_string(),
-                amount: Uint128::new(2),
-            }],
-        );
-        let init_msg = InstantiateMsg { count: 17 };
+    Ok(Response::default())
+}
 
-        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+pub fn try_transfer_ownership(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    new_owner: String,
+) -> Result<Response, ContractError> {
+    let new_owner = deps.api.addr_validate(&new_owner)?;
+    config(deps.storage).update(|mut state| {
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.pending_owner = Some(new_owner);
+        Ok(state)
+    })?;
 
-        // anyone can increment
-        let info = mock_info(
-            "anyone",
-            &[Coin {
-                denom: "token".to_string(),
-                amount: Uint128::new(2),
-            }],
-        );
+    deps.api.debug("ownership transfer proposed successfully");
+    Ok(Response::default())
+}
 
-        let exec_msg = ExecuteMsg::Increment {};
-        let _res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+pub fn try_accept_ownership(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    config(deps.storage).update(|mut state| {
+        if state.pending_owner.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.owner = info.sender.clone();
+        state.pending_owner = None;
+        Ok(state)
+    })?;
 
-        // should increase counter by 1
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: CountResponse = from_binary(&res).unwrap();
-        assert_eq!(18, value.count);
-    }
+    deps.api.debug("ownership accepted successfully");
+    Ok(Response::default())
+}
 
-    #[test]
-    fn generate() {
-        let mut deps = mock_dependencies_with_balance(&[Coin {
-            denom: "token".to_string(),
-            amount: Uint128::new(2),
-        }]);
-        let info = mock_info(
-            "creator",
-            &[Coin {
-                denom: "token".to_string(),
-                amount: Uint128::new(2),
-            }],
-        );
-        let init_msg = InstantiateMsg { count: 17 };
+pub fn try_set_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    admin: String,
+    add: bool,
+) -> Result<Response, ContractError> {
+    let admin = deps.api.addr_validate(&admin)?;
+    config(deps.storage).update(|mut state| {
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.admins.retain(|existing| *existing != admin);
+        if add {
+            state.admins.push(admin);
+        }
+        Ok(state)
+    })?;
 
-        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+    Ok(Response::default())
+}
 
-        let info = mock_info(
-            "anyone",
-            &[Coin {
-                denom: "token".to_string(),
-                amount: Uint128::new(2),
-            }],
-        );
+pub fn try_set_paused(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    config(deps.storage).update(|mut state| {
+        if !state.is_admin(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.paused = paused;
+        Ok(state)
+    })?;
 
-        let exec_msg = ExecuteMsg::Generate {};
-        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+    Ok(Response::new().add_attribute("paused", paused.to_string()))
+}
 
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMaps {}).unwrap();
-        let value: MapsResponse = from_binary(&res).unwrap();
-        assert_eq!(
-            vec!["1010101101100010111011001111111101110101000010101011101111000000"],
-            value.maps
-        );
+pub fn try_set_fee(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    fee: Option<Coin>,
+) -> Result<Response, ContractError> {
+    config(deps.storage).update(|mut state| {
+        if !state.is_admin(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.fee = fee;
+        Ok(state)
+    })?;
 
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMap { index: 0 }).unwrap();
-        let value: MapResponse = from_binary(&res).unwrap();
-        assert_eq!(
-            "1010101101100010111011001111111101110101000010101011101111000000",
-            value.map
-        );
-        assert_eq!(0, value.index);
+    Ok(Response::default())
+}
 
+pub fn try_set_limits(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    limits: Limits,
+) -> Result<Response, ContractError> {
+    config(deps.storage).update(|mut state| {
+        if !state.is_admin(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.limits = limits;
+        Ok(state)
+    })?;
+
+    Ok(Response::default())
+}
+
+pub fn try_set_tileset(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    name: String,
+    tileset: Option<Tileset>,
+) -> Result<Response, ContractError> {
+    let state = config_read(deps.storage).load()?;
+    if !state.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    match tileset {
+        Some(tileset) => {
+            tileset.validate().map_err(ContractError::InvalidTileset)?;
+            let hash = store_tileset(deps.storage, &tileset)?;
+            TILESETS.insert(deps.storage, &name, &hash)?;
+        }
+        None => TILESETS.remove(deps.storage, &name)?,
+    }
+
+    Ok(Response::default())
+}
+
+pub fn try_withdraw_fees(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let state = config_read(deps.storage).load()?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => state.owner,
+    };
+
+    // only what `Generate` charged, anything else sent to the contract stays put
+    let collected = COLLECTED_FEES.may_load(deps.storage)?.unwrap_or_default();
+    if collected.is_empty() {
+        return Ok(Response::default());
+    }
+    COLLECTED_FEES.save(deps.storage, &vec![])?;
+
+    Ok(Response::new().add_message(BankMsg::Send {
+        to_address: recipient.into_string(),
+        amount: collected,
+    }))
+}
+
+pub fn try_transfer_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    transfers: Vec<Transfer>,
+    batch: bool,
+) -> Result<Response, ContractError> {
+    for transfer in transfers {
+        let recipient = deps.api.addr_validate(&transfer.recipient)?;
+        for token_id in transfer.token_ids {
+            transfer_token(
+                deps.storage,
+                &env.block,
+                &info.sender,
+                &recipient,
+                &token_id,
+            )?;
+        }
+    }
+
+    deps.api.debug("tokens transferred successfully");
+    let status = ResponseStatus::Success;
+    answer(match batch {
+        false => ExecuteAnswer::TransferNft { status },
+        true => ExecuteAnswer::BatchTransferNft { status },
+    })
+}
+
+// transfers like `try_transfer_nft`, then calls back the receiving contracts
+pub fn try_send_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    sends: Vec<Send>,
+    batch: bool,
+) -> Result<Response, ContractError> {
+    let mut callbacks = vec![];
+    for send in sends {
+        let contract = deps.api.addr_validate(&send.contract)?;
+        let mut received = vec![];
+        for token_id in send.token_ids {
+            let from =
+                transfer_token(deps.storage, &env.block, &info.sender, &contract, &token_id)?;
+            received.push((from, token_id));
+        }
+
+        // contracts that neither registered nor were described get no callback
+        let receiver = match send.receiver_info {
+            Some(receiver_info) => Some(Receiver {
+                code_hash: receiver_info.recipient_code_hash,
+                batch: receiver_info
+                    .also_implements_batch_receive_nft
+                    .unwrap_or(false),
+            }),
+            None => RECEIVERS.get(deps.storage, &contract),
+        };
+        if let Some(receiver) = receiver {
+            let msgs = receiver_msgs(&receiver, &info.sender, received, send.msg);
+            for msg in msgs {
+                callbacks.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract.to_string(),
+                    code_hash: receiver.code_hash.clone(),
+                    msg: to_binary(&msg)?,
+                    funds: vec![],
+                }));
+            }
+        }
+    }
+
+    deps.api.debug("tokens sent successfully");
+    let status = ResponseStatus::Success;
+    let response = answer(match batch {
+        false => ExecuteAnswer::SendNft { status },
+        true => ExecuteAnswer::BatchSendNft { status },
+    })?;
+    Ok(response.add_messages(callbacks))
+}
+
+// one `ReceiveNft` per token, or one `BatchReceiveNft` per previous holder
+fn receiver_msgs(
+    receiver: &Receiver,
+    sender: &Addr,
+    received: Vec<(Addr, String)>,
+    msg: Option<Binary>,
+) -> Vec<ReceiverMsg> {
+    if !receiver.batch {
+        return received
+            .into_iter()
+            .map(|(from, token_id)| ReceiverMsg::ReceiveNft {
+                sender: from,
+                token_id,
+                msg: msg.clone(),
+            })
+            .collect();
+    }
+
+    let mut batches: Vec<(Addr, Vec<String>)> = vec![];
+    for (from, token_id) in received {
+        match batches.iter_mut().find(|(holder, _)| *holder == from) {
+            Some((_, token_ids)) => token_ids.push(token_id),
+            None => batches.push((from, vec![token_id])),
+        }
+    }
+    batches
+        .into_iter()
+        .map(|(from, token_ids)| ReceiverMsg::BatchReceiveNft {
+            sender: sender.clone(),
+            from,
+            token_ids,
+            msg: msg.clone(),
+        })
+        .collect()
+}
+
+// moves a token to `recipient` on behalf of `sender` and returns its previous holder
+fn transfer_token(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    sender: &Addr,
+    recipient: &Addr,
+    token_id: &str,
+) -> Result<Addr, ContractError> {
+    let (id, mut map_info) = load_token(storage, token_id)?;
+    let from = map_info.owner.clone();
+    if !may_transfer(storage, block, sender, id, &from) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // tokens kept through a `Clear` are only in the owners' token lists
+    if id >= config_read(storage).load()?.cleared_below {
+        remove_owner_map(storage, &from, id)?;
+        add_owner_map(storage, recipient, id)?;
+    }
+    remove_owner_token(storage, &from, id)?;
+    add_owner_token(storage, recipient, id)?;
+    // approvals were given by the previous holder
+    TOKEN_APPROVALS.remove(storage, &id)?;
+    map_info.owner = recipient.clone();
+    MAP_INFOS.insert(storage, &id, &map_info)?;
+
+    Ok(from)
+}
+
+// the holder, a spender it approved for the token or one of its operators
+fn may_transfer(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    sender: &Addr,
+    id: u32,
+    owner: &Addr,
+) -> bool {
+    *sender == *owner
+        || is_approved(
+            &TOKEN_APPROVALS.get(storage, &id).unwrap_or_default(),
+            sender,
+            block,
+        )
+        || is_operator(storage, block, owner, sender)
+}
+
+fn is_operator(storage: &dyn Storage, block: &BlockInfo, owner: &Addr, operator: &Addr) -> bool {
+    is_approved(
+        &OPERATORS.get(storage, owner).unwrap_or_default(),
+        operator,
+        block,
+    )
+}
+
+// approves `spender` for a token until `expires`, or revokes it when unset; the
+// holder and its operators may do so, approved spenders may not pass it on
+pub fn try_set_approval(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    token_id: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let spender = deps.api.addr_validate(&spender)?;
+    let (id, map_info) = load_token(deps.storage, &token_id)?;
+    if info.sender != map_info.owner
+        && !is_operator(deps.storage, &env.block, &map_info.owner, &info.sender)
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let status = ResponseStatus::Success;
+    let response = match expires {
+        Some(_) => ExecuteAnswer::Approve { status },
+        None => ExecuteAnswer::Revoke { status },
+    };
+    let mut approvals = TOKEN_APPROVALS.get(deps.storage, &id).unwrap_or_default();
+    set_approval(&mut approvals, spender, expires, &env.block);
+    TOKEN_APPROVALS.insert(deps.storage, &id, &approvals)?;
+
+    answer(response)
+}
+
+// approves `operator` for all of the sender's tokens until `expires`, or revokes it
+pub fn try_set_operator(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    operator: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let operator = deps.api.addr_validate(&operator)?;
+    let status = ResponseStatus::Success;
+    let response = match expires {
+        Some(_) => ExecuteAnswer::ApproveAll { status },
+        None => ExecuteAnswer::RevokeAll { status },
+    };
+    let mut operators = OPERATORS
+        .get(deps.storage, &info.sender)
+        .unwrap_or_default();
+    set_approval(&mut operators, operator, expires, &env.block);
+    OPERATORS.insert(deps.storage, &info.sender, &operators)?;
+
+    answer(response)
+}
+
+pub fn try_register_receive_nft(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    code_hash: String,
+    batch: bool,
+) -> Result<Response, ContractError> {
+    RECEIVERS.insert(deps.storage, &info.sender, &Receiver { code_hash, batch })?;
+
+    answer(ExecuteAnswer::RegisterReceiveNft {
+        status: ResponseStatus::Success,
+    })
+}
+
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    match msg {
+        QueryMsg::GetCount {} => Ok(to_binary(&query_count(deps)?)?),
+        QueryMsg::GetConfig {} => Ok(to_binary(&query_config(deps)?)?),
+        QueryMsg::GetFee {} => Ok(to_binary(&FeeResponse {
+            fee: config_read(deps.storage).load()?.fee,
+        })?),
+        QueryMsg::GetLimits {} => Ok(to_binary(&LimitsResponse {
+            limits: config_read(deps.storage).load()?.limits,
+        })?),
+        QueryMsg::GetTileset { name } => {
+            let hash = TILESETS
+                .get(deps.storage, &name)
+                .ok_or(ContractError::TilesetNotFound { name })?;
+            Ok(to_binary(&query_tileset(deps, hash)?)?)
+        }
+        QueryMsg::GetTilesetByHash { hash } => Ok(to_binary(&query_tileset(deps, hash)?)?),
+        QueryMsg::GetQuota { address } => {
+            let address = deps.api.addr_validate(&address)?;
+            let limits = config_read(deps.storage).load()?.limits;
+            Ok(to_binary(&quota(
+                deps,
+                &limits,
+                &address,
+                env.block.height,
+            )?)?)
+        }
+        QueryMsg::GetMaps {
+            start_after,
+            limit,
+            order,
+        } => Ok(to_binary(&query_maps(
+            deps,
+            start_after,
+            limit,
+            order.unwrap_or(Order::Ascending),
+        )?)?),
+        QueryMsg::GetMap { index, encoding } => query_map_encoded(deps, index, encoding, None),
+        QueryMsg::GetMapCount {} => Ok(to_binary(&query_map_count(deps)?)?),
+        QueryMsg::GetMapsByOwner {
+            owner,
+            start_after,
+            limit,
+            order,
+        } => Ok(to_binary(&query_maps_by_owner(
+            deps,
+            owner,
+            start_after,
+            limit,
+            order.unwrap_or(Order::Ascending),
+            None,
+        )?)?),
+        QueryMsg::GetMapInfo { id } => Ok(to_binary(&query_map_info(deps, id)?)?),
+        QueryMsg::GetPrivateMap {
+            index,
+            encoding,
+            address,
+            key,
+        } => {
+            let viewer = authenticate(deps, address, key)?;
+            query_map_encoded(deps, index, encoding, Some(&viewer))
+        }
+        QueryMsg::GetPrivateMapsByOwner {
+            owner,
+            start_after,
+            limit,
+            order,
+            address,
+            key,
+        } => {
+            let viewer = authenticate(deps, address, key)?;
+            Ok(to_binary(&query_maps_by_owner(
+                deps,
+                owner,
+                start_after,
+                limit,
+                order.unwrap_or(Order::Ascending),
+                Some(&viewer),
+            )?)?)
+        }
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, env, permit, query),
+        QueryMsg::ContractInfo {} => Ok(to_binary(&QueryAnswer::ContractInfo {
+            name: TOKEN_NAME.to_string(),
+            symbol: TOKEN_SYMBOL.to_string(),
+        })?),
+        // the count is public, so there is nothing to authenticate
+        QueryMsg::NumTokens { viewer: _ } => Ok(to_binary(&query_num_tokens(deps)?)?),
+        QueryMsg::Tokens {
+            owner,
+            viewer,
+            viewing_key,
+            start_after,
+            limit,
+        } => {
+            let viewer = match viewing_key {
+                Some(key) => Some(authenticate(
+                    deps,
+                    viewer.unwrap_or_else(|| owner.clone()),
+                    key,
+                )?),
+                None => None,
+            };
+            Ok(to_binary(&query_tokens(
+                deps,
+                &env.block,
+                owner,
+                start_after,
+                limit,
+                viewer.as_ref(),
+            )?)?)
+        }
+        QueryMsg::OwnerOf {
+            token_id,
+            viewer,
+            include_expired,
+        } => {
+            let viewer = authenticate_viewer(deps, viewer)?;
+            Ok(to_binary(&query_owner_of(
+                deps,
+                &env.block,
+                token_id,
+                viewer.as_ref(),
+                include_expired.unwrap_or(false),
+            )?)?)
+        }
+        QueryMsg::NftInfo { token_id } => Ok(to_binary(&query_nft_info(deps, token_id)?)?),
+        QueryMsg::PrivateMetadata { token_id, viewer } => {
+            let viewer = authenticate_viewer(deps, viewer)?;
+            Ok(to_binary(&query_private_metadata(
+                deps,
+                token_id,
+                viewer.as_ref(),
+            )?)?)
+        }
+        QueryMsg::ApprovedForAll {
+            owner,
+            viewing_key,
+            include_expired,
+        } => {
+            let operators = match viewing_key {
+                Some(key) => {
+                    let owner = authenticate(deps, owner, key)?;
+                    query_operators(deps, &env.block, &owner, include_expired.unwrap_or(false))
+                }
+                None => vec![],
+            };
+            Ok(to_binary(&QueryAnswer::ApprovedForAll { operators })?)
+        }
+    }
+}
+
+fn authenticate(deps: Deps, address: String, key: String) -> Result<Addr, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    ViewingKey::check(deps.storage, address.as_str(), &key)
+        .map_err(|_| ContractError::Unauthorized {})?;
+    Ok(address)
+}
+
+fn authenticate_viewer(
+    deps: Deps,
+    viewer: Option<ViewerInfo>,
+) -> Result<Option<Addr>, ContractError> {
+    viewer
+        .map(|viewer| authenticate(deps, viewer.address, viewer.viewing_key))
+        .transpose()
+}
+
+fn permit_queries(
+    deps: Deps,
+    env: Env,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> Result<Binary, ContractError> {
+    let viewer = validate(
+        deps,
+        PERMITS_PREFIX,
+        &permit,
+        env.contract.address.into_string(),
+        None,
+    )?;
+    if !permit.check_permission(&TokenPermissions::Owner) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let viewer = Addr::unchecked(viewer);
+
+    match query {
+        QueryWithPermit::GetMap { index, encoding } => {
+            query_map_encoded(deps, index, encoding, Some(&viewer))
+        }
+        QueryWithPermit::GetMapsByOwner {
+            owner,
+            start_after,
+            limit,
+            order,
+        } => Ok(to_binary(&query_maps_by_owner(
+            deps,
+            owner,
+            start_after,
+            limit,
+            order.unwrap_or(Order::Ascending),
+            Some(&viewer),
+        )?)?),
+        QueryWithPermit::PrivateMetadata { token_id } => Ok(to_binary(&query_private_metadata(
+            deps,
+            token_id,
+            Some(&viewer),
+        )?)?),
+        QueryWithPermit::Tokens {
+            owner,
+            start_after,
+            limit,
+        } => Ok(to_binary(&query_tokens(
+            deps,
+            &env.block,
+            owner,
+            start_after,
+            limit,
+            Some(&viewer),
+        )?)?),
+        QueryWithPermit::OwnerOf {
+            token_id,
+            include_expired,
+        } => Ok(to_binary(&query_owner_of(
+            deps,
+            &env.block,
+            token_id,
+            Some(&viewer),
+            include_expired.unwrap_or(false),
+        )?)?),
+        QueryWithPermit::ApprovedForAll {
+            owner,
+            include_expired,
+        } => {
+            if deps.api.addr_validate(&owner)? != viewer {
+                return Err(ContractError::Unauthorized {});
+            }
+            let operators =
+                query_operators(deps, &env.block, &viewer, include_expired.unwrap_or(false));
+            Ok(to_binary(&QueryAnswer::ApprovedForAll { operators })?)
+        }
+    }
+}
+
+fn query_count(deps: Deps) -> StdResult<CountResponse> {
+    let state = config_read(deps.storage).load()?;
+    Ok(CountResponse { count: state.count })
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let state = config_read(deps.storage).load()?;
+    Ok(ConfigResponse {
+        owner: state.owner,
+        pending_owner: state.pending_owner,
+        admins: state.admins,
+        storage_mode: state.storage_mode,
+        paused: state.paused,
+    })
+}
+
+// the global list only holds public maps
+fn query_maps(
+    deps: Deps,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+    order: Order,
+) -> Result<MapsResponse, ContractError> {
+    let map_count = MAP_COUNT.load(deps.storage)?;
+    let state = config_read(deps.storage).load()?;
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+
+    let ids: Box<dyn Iterator<Item = u32>> = match order {
+        Order::Ascending => {
+            let start = start_after.map_or(0, |id| id.saturating_add(1));
+            Box::new(start..map_count)
+        }
+        Order::Descending => {
+            let end = start_after.unwrap_or(map_count).min(map_count);
+            Box::new((0..end).rev())
+        }
+    };
+
+    // private, deleted and cleared maps are skipped, but only so many ids are looked
+    // at, so a page can come back short with `next` pointing at where the scan stopped
+    let mut maps = vec![];
+    let mut scanned = None;
+    for index in ids.take(MAX_PAGE_SCAN as usize) {
+        scanned = Some(index);
+        if !can_view(deps.storage, index, None) || state.is_cleared(deps.storage, index) {
+            continue;
+        }
+        if let Some(map) = MAPS.get(deps.storage, &index) {
+            maps.push(MapResponse {
+                index,
+                map: map.render(deps.storage)?,
+            });
+            if maps.len() == limit {
+                break;
+            }
+        }
+    }
+
+    let next = match (order, scanned) {
+        (Order::Ascending, Some(last)) if last + 1 < map_count => Some(last),
+        (Order::Descending, Some(last)) if last > 0 => Some(last),
+        _ => None,
+    };
+
+    Ok(MapsResponse { maps, next })
+}
+
+// maps `viewer` may not read are left out, so a page can come back short
+fn query_maps_by_owner(
+    deps: Deps,
+    owner: String,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+    order: Order,
+    viewer: Option<&Addr>,
+) -> Result<MapsResponse, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+
+    let (ids, more) = owner_maps_page(deps.storage, &owner, start_after, limit, order)?;
+    let next = match ids.last() {
+        Some(last) if more => Some(*last),
+        _ => None,
+    };
+
+    let maps = ids
+        .into_iter()
+        .filter(|index| can_view(deps.storage, *index, viewer))
+        .map(|index| query_map(deps, index, viewer))
+        .collect::<Result<Vec<_>, ContractError>>()?;
+
+    Ok(MapsResponse { maps, next })
+}
+
+fn load_map(deps: Deps, index: u32, viewer: Option<&Addr>) -> Result<StoredMap, ContractError> {
+    let state = config_read(deps.storage).load()?;
+    let map = MAPS
+        .get(deps.storage, &index)
+        .filter(|_| !state.is_cleared(deps.storage, index))
+        .ok_or(ContractError::MapNotFound { index })?;
+    if !can_view(deps.storage, index, viewer) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(map)
+}
+
+fn query_map_encoded(
+    deps: Deps,
+    index: u32,
+    encoding: Option<MapEncoding>,
+    viewer: Option<&Addr>,
+) -> Result<Binary, ContractError> {
+    let response = match encoding.unwrap_or(MapEncoding::Text) {
+        MapEncoding::Text => to_binary(&query_map(deps, index, viewer)?),
+        MapEncoding::Structured => to_binary(&query_map_data(deps, index, viewer)?),
+        MapEncoding::Compact => to_binary(&query_map_compact(deps, index, viewer)?),
+    }?;
+    Ok(response)
+}
+
+fn query_map(deps: Deps, index: u32, viewer: Option<&Addr>) -> Result<MapResponse, ContractError> {
+    let map = load_map(deps, index, viewer)?;
+    Ok(MapResponse {
+        index,
+        map: map.render(deps.storage)?,
+    })
+}
+
+fn query_map_data(
+    deps: Deps,
+    index: u32,
+    viewer: Option<&Addr>,
+) -> Result<MapDataResponse, ContractError> {
+    let map = load_map(deps, index, viewer)?;
+    Ok(MapDataResponse {
+        index,
+        data: map.data(deps.storage)?,
+    })
+}
+
+fn query_map_compact(
+    deps: Deps,
+    index: u32,
+    viewer: Option<&Addr>,
+) -> Result<CompactMapResponse, ContractError> {
+    let map = load_map(deps, index, viewer)?;
+    Ok(CompactMapResponse {
+        index,
+        map: map.encode(deps.storage)?,
+    })
+}
+
+fn query_tileset(deps: Deps, hash: Binary) -> Result<TilesetResponse, ContractError> {
+    let tileset = TILESET_CONTENTS.get(deps.storage, &hash).ok_or_else(|| {
+        ContractError::InvalidTileset(WfcError::UnknownTileset { hash: hash.clone() })
+    })?;
+    Ok(TilesetResponse { tileset, hash })
+}
+
+fn query_map_info(deps: Deps, id: u32) -> Result<MapInfoResponse, ContractError> {
+    let state = config_read(deps.storage).load()?;
+    let map_info = MAP_INFOS
+        .get(deps.storage, &id)
+        .filter(|_| !state.is_cleared(deps.storage, id))
+        .ok_or(ContractError::MapNotFound { index: id })?;
+    Ok(MapInfoResponse {
+        id,
+        creator: map_info.creator,
+        height: map_info.height,
+        time: map_info.time,
+        private: map_info.private,
+        owner: map_info.owner,
+        minted: map_info.minted,
+    })
+}
+
+// map ids double as token ids, for the maps that were minted
+fn load_token(storage: &dyn Storage, token_id: &str) -> Result<(u32, MapInfo), ContractError> {
+    token_id
+        .parse()
+        .ok()
+        .and_then(|id| Some((id, MAP_INFOS.get(storage, &id)?)))
+        .filter(|(_, map_info)| map_info.minted)
+        .ok_or_else(|| ContractError::TokenNotFound {
+            token_id: token_id.to_string(),
+        })
+}
+
+// approvals that lapsed are only listed when asked for
+fn shown_approvals(
+    approvals: Vec<Approval>,
+    block: &BlockInfo,
+    include_expired: bool,
+) -> Vec<Approval> {
+    approvals
+        .into_iter()
+        .filter(|approval| include_expired || !approval.expires.is_expired(block))
+        .collect()
+}
+
+fn query_num_tokens(deps: Deps) -> StdResult<QueryAnswer> {
+    Ok(QueryAnswer::NumTokens {
+        count: TOKEN_COUNT.load(deps.storage)?,
+    })
+}
+
+// the holder and its operators see every token, anyone else only the tokens of
+// maps they may view, so a page can come back short
+fn query_tokens(
+    deps: Deps,
+    block: &BlockInfo,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    viewer: Option<&Addr>,
+) -> Result<QueryAnswer, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let start_after = match start_after {
+        Some(token_id) => Some(load_token(deps.storage, &token_id)?.0),
+        None => None,
+    };
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    let sees_all = viewer
+        .is_some_and(|viewer| *viewer == owner || is_operator(deps.storage, block, &owner, viewer));
+
+    let ids = owner_tokens_page(deps.storage, &owner, start_after, limit)?;
+    let tokens = ids
+        .into_iter()
+        .filter(|id| sees_all || can_view(deps.storage, *id, viewer))
+        .map(|id| id.to_string())
+        .collect();
+
+    Ok(QueryAnswer::TokenList { tokens })
+}
+
+// the holder of a private map is only shown to those who may view or transfer it
+fn query_owner_of(
+    deps: Deps,
+    block: &BlockInfo,
+    token_id: String,
+    viewer: Option<&Addr>,
+    include_expired: bool,
+) -> Result<QueryAnswer, ContractError> {
+    let (id, map_info) = load_token(deps.storage, &token_id)?;
+    let owner = map_info.owner;
+    let may_see = can_view(deps.storage, id, viewer)
+        || viewer.is_some_and(|viewer| may_transfer(deps.storage, block, viewer, id, &owner));
+    if !may_see {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let approvals = match viewer {
+        Some(viewer) if *viewer == owner => {
+            let approvals = TOKEN_APPROVALS.get(deps.storage, &id).unwrap_or_default();
+            shown_approvals(approvals, block, include_expired)
+        }
+        _ => vec![],
+    };
+    Ok(QueryAnswer::OwnerOf { owner, approvals })
+}
+
+fn query_operators(
+    deps: Deps,
+    block: &BlockInfo,
+    owner: &Addr,
+    include_expired: bool,
+) -> Vec<Approval> {
+    let operators = OPERATORS.get(deps.storage, owner).unwrap_or_default();
+    shown_approvals(operators, block, include_expired)
+}
+
+fn query_nft_info(deps: Deps, token_id: String) -> Result<QueryAnswer, ContractError> {
+    let (id, map_info) = load_token(deps.storage, &token_id)?;
+    let map = MAPS
+        .get(deps.storage, &id)
+        .ok_or(ContractError::MapNotFound { index: id })?;
+    // the seed rebuilds the tiles, so for private maps it is only in the private metadata
+    let (seed, generator_version) = map.seed().filter(|_| !map_info.private).unzip();
+    Ok(QueryAnswer::NftInfo {
+        token_uri: None,
+        extension: MapMetadata {
+            size: map.size(deps.storage)?,
+            seed,
+            generator_version,
+        },
+    })
+}
+
+// public maps' tiles are readable without a viewer, as with `GetMap`
+fn query_private_metadata(
+    deps: Deps,
+    token_id: String,
+    viewer: Option<&Addr>,
+) -> Result<QueryAnswer, ContractError> {
+    let (id, _) = load_token(deps.storage, &token_id)?;
+    Ok(QueryAnswer::PrivateMetadata {
+        token_uri: None,
+        extension: load_map(deps, id, viewer)?.data(deps.storage)?,
+    })
+}
+
+fn query_map_count(deps: Deps) -> StdResult<MapCountResponse> {
+    let live = LIVE_MAP_COUNT.load(deps.storage)?;
+    Ok(MapCountResponse {
+        count: live as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsp::{MapError, Tile, TileGrid};
+    use crate::cave::CaveError;
+    use crate::msg::ReceiverInfo;
+    use crate::noise::NoiseError;
+    use crate::state::{LegacyState, CONFIG_KEY};
+    use crate::walk::{WalkBias, WalkError};
+    use crate::wfc::TilesetTile;
+    use cosmwasm_std::testing::*;
+    use cosmwasm_std::{coin, from_binary, SubMsg};
+    use cosmwasm_storage::singleton;
+    use secret_toolkit_permit::{PermitParams, PermitSignature, PubKey};
+
+    // the map produced by `mock_env()`'s randomness with the default parameters
+    const DEFAULT_MAP: &str = concat!(
+        "111111111111111111111111111111\n",
+        "111111000001111111111111111111\n",
+        "11xxx1000001xxx10001x111111x11\n",
+        "11xxx1000001xxx100011100001x11\n",
+        "11xxx1000001xxx100000000001x11\n",
+        "11xxx1101111xxx100011100001x11\n",
+        "11xxxx101xxxxxx10001x110111x11\n",
+        "11xxx11011xxxxx11111x110111111\n",
+        "11xxx10001xx111111111100000011\n",
+        "11xxx1000111100000000000000011\n",
+        "11xxx1000000000000000000000011\n",
+        "11xxx1000111101111111100000011\n",
+        "11xxx11111xx101xxxxxx100000011\n",
+        "11xxxxxxxxxx101xxxxxx111111111\n",
+        "11xxxxxxxxxx101111x11111111x11\n",
+        "111111111111100001110000001111\n",
+        "100000000000000000000000000111\n",
+        "100000011111100000000000000111\n",
+        "10000001xxxx100001110000000111\n",
+        "10000001xxxx101111x11111110111\n",
+        "10000001xxx11011111111xxx10111\n",
+        "10000001xxx10001100001xx110111\n",
+        "10000001xxx1000000000111100011\n",
+        "10000001xxx1000110000000000011\n",
+        "10000001xxx1000110000111100011\n",
+        "10000001xxx11011111111xx110111\n",
+        "11011111xxxx101xxxxxxxxxx10111\n",
+        "1101xxxxxxxx101xxxxxxxxxx10111\n",
+        "1101xxxxxx1110111111111x110111\n",
+        "110111111110000110000011100011\n",
+        "100000000110000000000000000011\n",
+        "100000000110000000000000000011\n",
+        "100000000111100110000011100011\n",
+        "1101110111xx10011000001x100011\n",
+        "1101x101xxxx10011000001x110111\n",
+        "11011101xxxx10011111111xx10111\n",
+        "11000001xxxx10011xxxxxxxx10111\n",
+        "11000001xxxx10001xxx1111110111\n",
+        "11000001xxxx10001xxx1000000001\n",
+        "11000001xxxx10001xxx1000000001\n",
+        "11000001xxxx10001xxx1000000001\n",
+        "11011011xxxx10011xxx1000000001\n",
+        "1101101xxxx110011xxx1000000001\n",
+        "1101101111x100001xxx1000000001\n",
+        "1000000001x1000011111000000001\n",
+        "1000000001x1000000000011111111\n",
+        "1000000001x100001111111xxxxx11\n",
+        "1000000001x111111xxxxxxxxxxx11\n",
+        "100000000111111111111111111111\n",
+        "111111111111111111111111111111\n",
+    );
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies();
+        let info = mock_info(
+            "creator",
+            &[Coin {
+                denom: "earth".to_string(),
+                amount: Uint128::new(1000),
+            }],
+        );
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        assert_eq!(0, res.messages.len());
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: CountResponse = from_binary(&res).unwrap();
+        assert_eq!(17, value.count);
+    }
+
+    #[test]
+    fn increment() {
+        let mut deps = mock_dependencies_with_balance(&[Coin {
+            denom: "token".to_string(),
+            amount: Uint128::new(2),
+        }]);
+        let info = mock_info(
+            "creator",
+            &[Coin {
+                denom: "token".to_string(),
+                amount: Uint128::new(2),
+            }],
+        );
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // anyone can increment
+        let info = mock_info(
+            "anyone",
+            &[Coin {
+                denom: "token".to_string(),
+                amount: Uint128::new(2),
+            }],
+        );
+
+        let exec_msg = ExecuteMsg::Increment {};
+        let _res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        // should increase counter by 1
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: CountResponse = from_binary(&res).unwrap();
+        assert_eq!(18, value.count);
+    }
+
+    #[test]
+    fn generate() {
+        let mut deps = mock_dependencies_with_balance(&[Coin {
+            denom: "token".to_string(),
+            amount: Uint128::new(2),
+        }]);
+        let info = mock_info(
+            "creator",
+            &[Coin {
+                denom: "token".to_string(),
+                amount: Uint128::new(2),
+            }],
+        );
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let info = mock_info(
+            "anyone",
+            &[Coin {
+                denom: "token".to_string(),
+                amount: Uint128::new(2),
+            }],
+        );
+
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMaps {
+                start_after: None,
+                limit: None,
+                order: None,
+            },
+        )
+        .unwrap();
+        let value: MapsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![MapResponse {
+                index: 0,
+                map: DEFAULT_MAP.to_string()
+            }],
+            value.maps
+        );
+        assert_eq!(None, value.next);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 0,
+                encoding: None,
+            },
+        )
+        .unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        assert_eq!(DEFAULT_MAP, value.map);
+        assert_eq!(0, value.index);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.count);
+    }
+
+    #[test]
+    fn generate_with_parameters() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: Some(Size::new(20, 24)),
+            min_room_size: Some(Size::new(6, 7)),
+            max_room_size: Some(Size::new(9, 10)),
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 0,
+                encoding: None,
+            },
+        )
+        .unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        let rows: Vec<&str> = value.map.lines().collect();
+        assert_eq!(24, rows.len());
+        assert!(rows.iter().all(|row| row.len() == 20));
+    }
+
+    #[test]
+    fn generate_with_invalid_parameters() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let invalid = [
+            (Some(Size::new(19, 50)), None, None),
+            (Some(Size::new(30, 129)), None, None),
+            (Some(Size::new(u32::MAX, u32::MAX)), None, None),
+            (None, Some(Size::new(5, 6)), None),
+            (None, None, Some(Size::new(6, 14))),
+            (None, None, Some(Size::new(30, 14))),
+        ];
+        for (size, min_room_size, max_room_size) in invalid {
+            let info = mock_info("anyone", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size,
+                min_room_size,
+                max_room_size,
+                private: None,
+                mint: None,
+                generator: None,
+            };
+
+            let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+
+            match res {
+                Err(ContractError::InvalidMapParameters(_)) => {}
+                _ => panic!("Must return invalid map parameters error"),
+            }
+        }
+
+        // nothing was stored
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.count);
+    }
+
+    #[test]
+    fn generate_without_randomness() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.random = None;
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+
+        let res = execute(deps.as_mut(), env, info, exec_msg.clone());
+
+        assert_eq!(Err(ContractError::MissingRandomness {}), res);
+
+        let mut env = mock_env();
+        env.block.random = Some(Binary::default());
+        let info = mock_info("anyone", &[]);
+
+        let res = execute(deps.as_mut(), env, info, exec_msg);
+
+        assert_eq!(Err(ContractError::MissingRandomness {}), res);
+    }
+
+    #[test]
+    fn instantiate_without_randomness() {
+        // the viewing key seed would otherwise be empty, and keys guessable
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        env.block.random = None;
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env, info, init_msg);
+        assert_eq!(Err(ContractError::MissingRandomness {}), res);
+    }
+
+    #[test]
+    fn generate_uses_all_randomness_bytes() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // two seeds that only differ in their last byte
+        for last in [0u8, 1u8] {
+            let mut random = [7u8; 32];
+            random[31] = last;
+            let mut env = mock_env();
+            env.block.random = Some(Binary::from(random));
+
+            let info = mock_info("anyone", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: None,
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: None,
+                generator: None,
+            };
+            execute(deps.as_mut(), env, info, exec_msg).unwrap();
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 0,
+                encoding: None,
+            },
+        )
+        .unwrap();
+        let first: MapResponse = from_binary(&res).unwrap();
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 1,
+                encoding: None,
+            },
+        )
+        .unwrap();
+        let second: MapResponse = from_binary(&res).unwrap();
+        assert_ne!(first.map, second.map);
+    }
+
+    #[test]
+    fn query_map_structured() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: Some(MapEncoding::Structured),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: MapDataResponse = from_binary(&res).unwrap();
+        let data = value.data;
+
+        assert_eq!(0, value.index);
+        assert_eq!((30, 50), (data.width, data.height));
+        assert_eq!(mock_env().block.random, data.seed);
+        assert_eq!(Some(GeneratorVersion::V2), data.generator_version);
+
+        let rendered: String = data
+            .tiles
+            .chunks(data.width as usize)
+            .map(|row| row.iter().map(|tile| tile.to_string()).collect::<String>() + "\n")
+            .collect();
+        assert_eq!(DEFAULT_MAP, rendered);
+
+        assert!(!data.rooms.is_empty());
+        assert!(!data.corridors.is_empty());
+        for room in &data.rooms {
+            let (position, size) = (room.position(), room.size());
+            for y in position.y()..position.y() + size.height() {
+                for x in position.x()..position.x() + size.width() {
+                    assert_eq!(Tile::Floor, data.tiles[(y * data.width + x) as usize]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn query_map_compact() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: Some(MapEncoding::Compact),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: CompactMapResponse = from_binary(&res).unwrap();
+
+        assert!(value.map.len() < DEFAULT_MAP.len() / 3);
+        let grid = TileGrid::decode(value.map.as_slice()).unwrap();
+        assert_eq!(DEFAULT_MAP, grid.to_string());
+    }
+
+    #[test]
+    fn seed_storage_mode() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: Some(StorageMode::Seed),
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let params = MapParams {
+            size: DEFAULT_MAP_SIZE,
+            min_room_size: DEFAULT_MIN_ROOM_SIZE,
+            max_room_size: DEFAULT_MAX_ROOM_SIZE,
+        };
+        let stored = MAPS.get(deps.as_ref().storage, &0).unwrap();
+        assert_eq!(
+            StoredMap::V5 {
+                seed: mock_env().block.random.unwrap(),
+                generator_version: GeneratorVersion::CURRENT,
+                params: GeneratorParams::Bsp(params),
+            },
+            stored
+        );
+
+        // the map is rebuilt from its seed on every query
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 0,
+                encoding: None,
+            },
+        )
+        .unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        assert_eq!(DEFAULT_MAP, value.map);
+
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: Some(MapEncoding::Structured),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: MapDataResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(GeneratorParams::Bsp(params)), value.data.generator);
+        assert!(!value.data.rooms.is_empty());
+
+        // parameters are still validated up front
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: Some(Size::new(10, 10)),
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(
+            Err(ContractError::InvalidMapParameters(
+                MapError::MapTooSmall.into()
+            )),
+            res
+        );
+
+        // and the size is capped, as every query regenerates the map
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: Some(Size::new(65, 64)),
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(
+            Err(ContractError::SeedMapTooLarge {
+                max_cells: MAX_SEED_MAP_CELLS
+            }),
+            res
+        );
+    }
+
+    #[test]
+    fn generate_cave() {
+        let cave = GeneratorKind::Cave {
+            fill_percent: None,
+            iterations: None,
+            birth_limit: None,
+            survival_limit: None,
+        };
+        let params = GeneratorParams::Cave(CaveParams {
+            size: DEFAULT_MAP_SIZE,
+            fill_percent: DEFAULT_CAVE_FILL_PERCENT,
+            iterations: DEFAULT_CAVE_ITERATIONS,
+            birth_limit: DEFAULT_CAVE_BIRTH_LIMIT,
+            survival_limit: DEFAULT_CAVE_SURVIVAL_LIMIT,
+        });
+
+        let mut maps = vec![];
+        for storage_mode in [StorageMode::Tiles, StorageMode::Seed] {
+            let mut deps = mock_dependencies();
+            let info = mock_info("creator", &[]);
+            let init_msg = InstantiateMsg {
+                count: 17,
+                storage_mode: Some(storage_mode),
+                fee: None,
+                limits: None,
+            };
+            instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+            let info = mock_info("anyone", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: None,
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: None,
+                generator: Some(cave.clone()),
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+            let stored = MAPS.get(deps.as_ref().storage, &0).unwrap();
+            if storage_mode == StorageMode::Seed {
+                assert_eq!(
+                    StoredMap::V5 {
+                        seed: mock_env().block.random.unwrap(),
+                        generator_version: GeneratorVersion::CURRENT,
+                        params: params.clone(),
+                    },
+                    stored
+                );
+            }
+
+            let msg = QueryMsg::GetMap {
+                index: 0,
+                encoding: Some(MapEncoding::Structured),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: MapDataResponse = from_binary(&res).unwrap();
+            assert_eq!(DEFAULT_MAP_SIZE.width(), value.data.width);
+            assert_eq!(DEFAULT_MAP_SIZE.height(), value.data.height);
+            assert!(value.data.rooms.is_empty());
+            assert!(value.data.tiles.contains(&Tile::Floor));
+            if storage_mode == StorageMode::Seed {
+                assert_eq!(Some(params.clone()), value.data.generator);
+            }
+            maps.push(value.data.tiles);
+        }
+        // both storage modes yield the same cave
+        assert_eq!(maps[0], maps[1]);
+
+        // cave parameters are validated too
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: Some(GeneratorKind::Cave {
+                fill_percent: Some(101),
+                iterations: None,
+                birth_limit: None,
+                survival_limit: None,
+            }),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(
+            Err(ContractError::InvalidMapParameters(
+                CaveError::FillPercentTooLarge.into()
+            )),
+            res
+        );
+    }
+
+    #[test]
+    fn generate_walk() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: Some(StorageMode::Seed),
+            fee: None,
+            limits: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: Some(GeneratorKind::Walk {
+                floor_percent: None,
+                walkers: Some(2),
+                bias: Some(WalkBias::Edges),
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let params = GeneratorParams::Walk(WalkParams {
+            size: DEFAULT_MAP_SIZE,
+            floor_percent: DEFAULT_WALK_FLOOR_PERCENT,
+            walkers: 2,
+            bias: Some(WalkBias::Edges),
+        });
+        let stored = MAPS.get(deps.as_ref().storage, &0).unwrap();
+        assert_eq!(
+            StoredMap::V5 {
+                seed: mock_env().block.random.unwrap(),
+                generator_version: GeneratorVersion::CURRENT,
+                params: params.clone(),
+            },
+            stored
+        );
+
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: Some(MapEncoding::Structured),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: MapDataResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(params), value.data.generator);
+        let interior = (DEFAULT_MAP_SIZE.width() - 2) * (DEFAULT_MAP_SIZE.height() - 2);
+        let floors = value
+            .data
+            .tiles
+            .iter()
+            .filter(|tile| **tile == Tile::Floor)
+            .count();
+        assert_eq!(
+            (interior * DEFAULT_WALK_FLOOR_PERCENT / 100) as usize,
+            floors
+        );
+
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: Some(GeneratorKind::Walk {
+                floor_percent: None,
+                walkers: Some(0),
+                bias: None,
+            }),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(
+            Err(ContractError::InvalidMapParameters(
+                WalkError::InvalidWalkerCount.into()
+            )),
+            res
+        );
+    }
+
+    #[test]
+    fn generate_wfc() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: Some(StorageMode::Seed),
+            fee: None,
+            limits: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let tile = |tile| TilesetTile { tile, weight: 1 };
+        let tileset = Tileset {
+            tiles: vec![tile(Tile::Floor), tile(Tile::Wall)],
+            horizontal: vec![(0, 0), (0, 1), (1, 0), (1, 1)],
+            vertical: vec![(0, 0), (1, 1)],
+        };
+        let generate = |tileset: &str| ExecuteMsg::Generate {
+            size: Some(Size::new(8, 6)),
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: Some(GeneratorKind::Wfc {
+                tileset: tileset.to_string(),
+            }),
+        };
+
+        // only admins upload tilesets, and only valid ones
+        let exec_msg = ExecuteMsg::SetTileset {
+            name: "columns".to_string(),
+            tileset: tileset.clone(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            exec_msg,
+        );
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let exec_msg = ExecuteMsg::SetTileset {
+            name: "columns".to_string(),
+            tileset: Tileset {
+                vertical: vec![(0, 2)],
+                ..tileset.clone()
+            },
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        );
+        assert_eq!(
+            Err(ContractError::InvalidTileset(WfcError::UnknownTilesetTile)),
+            res
+        );
+
+        let exec_msg = ExecuteMsg::SetTileset {
+            name: "columns".to_string(),
+            tileset: tileset.clone(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        let msg = QueryMsg::GetTileset {
+            name: "columns".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TilesetResponse = from_binary(&res).unwrap();
+        assert_eq!(tileset, value.tileset);
+        let hash = value.hash;
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            generate("rows"),
+        );
+        assert_eq!(
+            Err(ContractError::TilesetNotFound {
+                name: "rows".to_string()
+            }),
+            res
+        );
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            generate("columns"),
+        )
+        .unwrap();
+        // the map only keeps the hash of its tileset
+        let params = GeneratorParams::Wfc(WfcParams {
+            size: Size::new(8, 6),
+            tileset: hash.clone(),
+        });
+        let stored = MAPS.get(deps.as_ref().storage, &0).unwrap();
+        assert_eq!(
+            StoredMap::V5 {
+                seed: mock_env().block.random.unwrap(),
+                generator_version: GeneratorVersion::CURRENT,
+                params,
+            },
+            stored
+        );
+
+        // removing the tileset leaves maps generated from it alone
+        let exec_msg = ExecuteMsg::RemoveTileset {
+            name: "columns".to_string(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        let msg = QueryMsg::GetTileset {
+            name: "columns".to_string(),
+        };
+        assert_eq!(
+            Err(ContractError::TilesetNotFound {
+                name: "columns".to_string()
+            }),
+            query(deps.as_ref(), mock_env(), msg)
+        );
+        let msg = QueryMsg::GetTilesetByHash { hash: hash.clone() };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TilesetResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            TilesetResponse {
+                tileset: tileset.clone(),
+                hash
+            },
+            value
+        );
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        let rows: Vec<&str> = value.map.lines().collect();
+        assert_eq!(6, rows.len());
+        assert!(rows.iter().all(|row| *row == rows[0]));
+
+        // a tileset that can never be laid out fails generation without retrying
+        let exec_msg = ExecuteMsg::SetTileset {
+            name: "broken".to_string(),
+            tileset: Tileset {
+                horizontal: vec![(0, 1)],
+                ..tileset
+            },
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            generate("broken"),
+        );
+        assert_eq!(Err(ContractError::Contradiction { attempts: 1 }), res);
+    }
+
+    #[test]
+    fn generate_terrain() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: Some(StorageMode::Seed),
+            fee: None,
+            limits: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let generate = |sea_level, beach_level| ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: Some(GeneratorKind::Noise {
+                octaves: None,
+                scale: None,
+                sea_level,
+                beach_level,
+                mountain_level: None,
+                forest_moisture: None,
+            }),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            generate(None, None),
+        )
+        .unwrap();
+
+        let params = GeneratorParams::Noise(NoiseParams {
+            size: DEFAULT_MAP_SIZE,
+            octaves: DEFAULT_NOISE_OCTAVES,
+            scale: DEFAULT_NOISE_SCALE,
+            sea_level: DEFAULT_SEA_LEVEL,
+            beach_level: DEFAULT_BEACH_LEVEL,
+            mountain_level: DEFAULT_MOUNTAIN_LEVEL,
+            forest_moisture: DEFAULT_FOREST_MOISTURE,
+        });
+        let stored = MAPS.get(deps.as_ref().storage, &0).unwrap();
+        assert_eq!(
+            StoredMap::V5 {
+                seed: mock_env().block.random.unwrap(),
+                generator_version: GeneratorVersion::CURRENT,
+                params,
+            },
+            stored
+        );
+
+        // terrain is made of biomes only
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        assert!(value.map.contains('~'));
+        assert!(value
+            .map
+            .chars()
+            .all(|c| matches!(c, '~' | ':' | ',' | 'T' | '^' | '\n')));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            generate(Some(50), Some(40)),
+        );
+        assert_eq!(
+            Err(ContractError::InvalidMapParameters(
+                NoiseError::InvalidBiomeThresholds.into()
+            )),
+            res
+        );
+    }
+
+    #[test]
+    fn query_legacy_map() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // a 3 wide, 2 high map in the old column-major layout
+        let legacy = StoredMap::V1("11\n01\n11\n".to_string());
+        MAPS.insert(deps.as_mut().storage, &0, &legacy).unwrap();
+        MAP_COUNT.save(deps.as_mut().storage, &1).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 0,
+                encoding: None,
+            },
+        )
+        .unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        assert_eq!("101\n111\n", value.map);
+
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: Some(MapEncoding::Structured),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: MapDataResponse = from_binary(&res).unwrap();
+        assert_eq!((3, 2), (value.data.width, value.data.height));
+        assert_eq!(Tile::Floor, value.data.tiles[1]);
+        assert!(value.data.rooms.is_empty());
+        assert_eq!(None, value.data.seed);
+    }
+
+    #[test]
+    fn query_missing_map() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 3,
+                encoding: None,
+            },
+        );
+
+        assert_eq!(Err(ContractError::MapNotFound { index: 3 }), res);
+    }
+
+    #[test]
+    fn clear() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // maps 0 and 1, and token 2
+        let generate = |mint| ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: Some(false),
+            mint: Some(mint),
+            generator: None,
+        };
+        for mint in [false, false, true] {
+            let info = mock_info("anyone", &[]);
+            execute(deps.as_mut(), mock_env(), info, generate(mint)).unwrap();
+        }
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.count);
+
+        // not anyone can clear
+        let info = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Clear {});
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.count);
+
+        // only the contract owner can
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Clear {}).unwrap();
+
+        // the token is kept
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.count);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 1,
+                encoding: None,
+            },
+        );
+        assert_eq!(Err(ContractError::MapNotFound { index: 1 }), res);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMaps {
+                start_after: None,
+                limit: None,
+                order: None,
+            },
+        )
+        .unwrap();
+        let value: MapsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![2],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        let msg = QueryMsg::GetMapsByOwner {
+            owner: "anyone".to_string(),
+            start_after: None,
+            limit: None,
+            order: None,
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![2],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(ContractError::MapNotFound { index: 0 }),
+            query(deps.as_ref(), mock_env(), QueryMsg::GetMapInfo { id: 0 })
+        );
+        let info = mock_info("anyone", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::DeleteMap { id: 1 },
+        );
+        assert_eq!(Err(ContractError::MapNotFound { index: 1 }), res);
+
+        let msg = QueryMsg::Tokens {
+            owner: "anyone".to_string(),
+            viewer: None,
+            viewing_key: None,
+            start_after: None,
+            limit: None,
+        };
+        let value: QueryAnswer =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            QueryAnswer::TokenList {
+                tokens: vec!["2".to_string()]
+            },
+            value
+        );
+
+        // ids are not reused, and new maps follow the kept token in the owner's list
+        let info = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, generate(false)).unwrap();
+        assert_eq!("3", res.attributes[0].value);
+
+        let msg = QueryMsg::GetMapsByOwner {
+            owner: "anyone".to_string(),
+            start_after: None,
+            limit: None,
+            order: Some(Order::Descending),
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![3, 2],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+        let msg = QueryMsg::GetMapsByOwner {
+            owner: "anyone".to_string(),
+            start_after: Some(2),
+            limit: Some(1),
+            order: None,
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![3],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        // the kept token still counts towards the quota
+        let msg = QueryMsg::GetQuota {
+            address: "anyone".to_string(),
+        };
+        let value: QuotaResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(2, value.maps);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.count);
+    }
+
+    #[test]
+    fn delete_map() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        for owner in ["alice", "alice", "bob"] {
+            let info = mock_info(owner, &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: None,
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+
+        // only the map's owner can delete it, not even the contract owner
+        for sender in ["bob", "creator"] {
+            let info = mock_info(sender, &[]);
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::DeleteMap { id: 0 },
+            );
+            assert_eq!(Err(ContractError::Unauthorized {}), res);
+        }
+
+        // maps stored without a creator cannot be deleted
+        let legacy = StoredMap::V2("101\n111\n".to_string());
+        MAPS.insert(deps.as_mut().storage, &3, &legacy).unwrap();
+        MAP_COUNT.save(deps.as_mut().storage, &4).unwrap();
+        LIVE_MAP_COUNT.save(deps.as_mut().storage, &4).unwrap();
+        let info = mock_info("alice", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::DeleteMap { id: 3 },
+        );
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let info = mock_info("alice", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::DeleteMap { id: 0 },
+        )
+        .unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 0,
+                encoding: None,
+            },
+        );
+        assert_eq!(Err(ContractError::MapNotFound { index: 0 }), res);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapInfo { id: 0 });
+        assert_eq!(Err(ContractError::MapNotFound { index: 0 }), res);
+
+        let msg = QueryMsg::GetMapsByOwner {
+            owner: "alice".to_string(),
+            start_after: None,
+            limit: None,
+            order: None,
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![1],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        // the global list skips the gap
+        let msg = QueryMsg::GetMaps {
+            start_after: None,
+            limit: None,
+            order: None,
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![1, 2, 3],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        // and the count leaves it out
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.count);
+
+        // deleting it again reports the map as missing
+        let info = mock_info("alice", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::DeleteMap { id: 0 },
+        );
+        assert_eq!(Err(ContractError::MapNotFound { index: 0 }), res);
+    }
+
+    #[test]
+    fn private_maps_with_viewing_keys() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // alice generates a private map 0 and a public map 1
+        for private in [Some(true), None] {
+            let info = mock_info("alice", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private,
+                mint: None,
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+
+        let get_map = QueryMsg::GetMap {
+            index: 0,
+            encoding: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), get_map);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapInfo { id: 0 }).unwrap();
+        let value: MapInfoResponse = from_binary(&res).unwrap();
+        assert!(value.private);
+
+        // public listings leave the private map out
+        let msg = QueryMsg::GetMaps {
+            start_after: None,
+            limit: None,
+            order: None,
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![1],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        let msg = QueryMsg::GetMapsByOwner {
+            owner: "alice".to_string(),
+            start_after: None,
+            limit: None,
+            order: None,
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![1],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::CreateViewingKey {
+            entropy: "fog of war".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let alice_key = match from_binary(&res.data.unwrap()).unwrap() {
+            ExecuteAnswer::ViewingKey { key } => key,
+            _ => panic!("Must return the viewing key"),
+        };
+
+        let private_map = |address: &str, key: &str| QueryMsg::GetPrivateMap {
+            index: 0,
+            encoding: None,
+            address: address.to_string(),
+            key: key.to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), private_map("alice", &alice_key)).unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.index);
+
+        let res = query(deps.as_ref(), mock_env(), private_map("alice", "guess"));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        // a valid key does not open other players' private maps
+        let info = mock_info("bob", &[]);
+        let exec_msg = ExecuteMsg::SetViewingKey {
+            key: "bob's key".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), private_map("bob", "bob's key"));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        // until the owner authorizes them
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::AddViewer {
+            viewer: "bob".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        query(deps.as_ref(), mock_env(), private_map("bob", "bob's key")).unwrap();
+
+        let msg = QueryMsg::GetPrivateMapsByOwner {
+            owner: "alice".to_string(),
+            start_after: None,
+            limit: None,
+            order: None,
+            address: "bob".to_string(),
+            key: "bob's key".to_string(),
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![0, 1],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::RemoveViewer {
+            viewer: "bob".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), private_map("bob", "bob's key"));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+    }
+
+    #[test]
+    fn private_maps_with_permits() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // owner permits for `cosmos2contract`, the mock contract address, signed by two accounts
+        let permit = |pub_key: &str, signature: &str| Permit {
+            params: PermitParams {
+                allowed_tokens: vec!["cosmos2contract".to_string()],
+                permit_name: "map-gen".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![TokenPermissions::Owner],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64(pub_key).unwrap(),
+                },
+                signature: Binary::from_base64(signature).unwrap(),
+            },
+        };
+        let owner = "secret1g29h2yf26y4nqfnt6mn99fpfnasmagz40e5alr";
+        let owner_permit = permit(
+            "A4tT36sFdi5cHK2uZ1MMPrms/nHr3EPh/itcDk+209P5",
+            "slJsiiuRLJaAC0OdjP/RXhDV3LU8M+QPpKPkB90GaXkVOOBk/2RfQioH70g6Rw82kIpvm7gE19j9cEDF4rGIdw==",
+        );
+        let other_permit = permit(
+            "A8E5KRnwplOgAhA8SfLBAN25MrAoq5W+5zQFXMKlfzkd",
+            "Xf4bSxgFqIO8u1qc/uqBJC29AgyB/yaiAuPhSrobDWZWHwoXwvy8y5r6j8BZG2KDqjk6Skgbzw+VQYlxWBTy7w==",
+        );
+
+        let info = mock_info(owner, &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: Some(Size::new(20, 20)),
+            min_room_size: None,
+            max_room_size: None,
+            private: Some(true),
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let with_permit = |permit: &Permit| QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::GetMap {
+                index: 0,
+                encoding: Some(MapEncoding::Compact),
+            },
+        };
+        let res = query(deps.as_ref(), mock_env(), with_permit(&owner_permit)).unwrap();
+        let value: CompactMapResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.index);
+
+        let msg = QueryMsg::WithPermit {
+            permit: owner_permit.clone(),
+            query: QueryWithPermit::GetMapsByOwner {
+                owner: owner.to_string(),
+                start_after: None,
+                limit: None,
+                order: None,
+            },
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![0],
+            value.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+
+        let res = query(deps.as_ref(), mock_env(), with_permit(&other_permit));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        // permits are bound to this contract
+        let mut env = mock_env();
+        env.contract.address = Addr::unchecked("cosmos2other");
+        let res = query(deps.as_ref(), env, with_permit(&owner_permit));
+        assert!(matches!(res, Err(ContractError::Std(_))));
+
+        // and stop working once revoked
+        let info = mock_info(owner, &[]);
+        let exec_msg = ExecuteMsg::RevokePermit {
+            permit_name: "map-gen".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), with_permit(&owner_permit));
+        assert!(matches!(res, Err(ContractError::Std(_))));
+    }
+
+    #[test]
+    fn mint_and_transfer_nft() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // alice mints maps 0 and 2, map 1 is not a token and map 2 is public
+        for (mint, private) in [(Some(true), None), (None, None), (Some(true), Some(false))] {
+            let info = mock_info("alice", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: None,
+                min_room_size: None,
+                max_room_size: None,
+                private,
+                mint,
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::NumTokens { viewer: None },
+        )
+        .unwrap();
+        assert_eq!(
+            QueryAnswer::NumTokens { count: 2 },
+            from_binary(&res).unwrap()
+        );
+
+        for owner in ["alice", "bob"] {
+            let info = mock_info(owner, &[]);
+            let exec_msg = ExecuteMsg::SetViewingKey {
+                key: format!("{} key", owner),
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+        let viewer = |address: &str| ViewerInfo {
+            address: address.to_string(),
+            viewing_key: format!("{} key", address),
+        };
+
+        // anyone sees the tokens of public maps, the holder all of them
+        let tokens = |deps: Deps, owner: &str, viewer: Option<&str>| -> Vec<String> {
+            let msg = QueryMsg::Tokens {
+                owner: owner.to_string(),
+                viewer: None,
+                viewing_key: viewer.map(|viewer| format!("{} key", viewer)),
+                start_after: None,
+                limit: None,
+            };
+            let res = query(deps, mock_env(), msg).unwrap();
+            match from_binary(&res).unwrap() {
+                QueryAnswer::TokenList { tokens } => tokens,
+                _ => panic!("Must return a token list"),
+            }
+        };
+        assert_eq!(
+            vec!["0", "2"],
+            tokens(deps.as_ref(), "alice", Some("alice"))
+        );
+        assert_eq!(vec!["2"], tokens(deps.as_ref(), "alice", None));
+        let msg = QueryMsg::Tokens {
+            owner: "alice".to_string(),
+            viewer: None,
+            viewing_key: Some("bob key".to_string()),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        // public metadata has the size, and the seed of public maps, but not the tiles
+        let nft_info = |token_id: &str| -> MapMetadata {
+            let msg = QueryMsg::NftInfo {
+                token_id: token_id.to_string(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            match from_binary(&res).unwrap() {
+                QueryAnswer::NftInfo { extension, .. } => extension,
+                _ => panic!("Must return the token info"),
+            }
+        };
+        let value = nft_info("0");
+        assert_eq!(DEFAULT_MAP_SIZE, value.size);
+        assert_eq!(None, value.seed);
+        assert_eq!(None, value.generator_version);
+        let value = nft_info("2");
+        assert_eq!(mock_env().block.random, value.seed);
+        assert_eq!(Some(GeneratorVersion::CURRENT), value.generator_version);
+
+        let msg = QueryMsg::NftInfo {
+            token_id: "1".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg);
+        assert_eq!(
+            Err(ContractError::TokenNotFound {
+                token_id: "1".to_string()
+            }),
+            res
+        );
+
+        // minted maps are private unless generated otherwise
+        let msg = QueryMsg::GetMap {
+            index: 0,
+            encoding: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let private_metadata = |owner: Option<&str>| QueryMsg::PrivateMetadata {
+            token_id: "0".to_string(),
+            viewer: owner.map(viewer),
+        };
+        let res = query(deps.as_ref(), mock_env(), private_metadata(None));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+        let res = query(deps.as_ref(), mock_env(), private_metadata(Some("alice"))).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::PrivateMetadata { extension, .. } => {
+                assert_eq!(1500, extension.tiles.len());
+                assert_eq!(mock_env().block.random, extension.seed);
+            }
+            _ => panic!("Must return the private metadata"),
+        }
+
+        // only the owner can transfer, and only tokens
+        let info = mock_info("bob", &[]);
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: "bob".to_string(),
+            token_id: "0".to_string(),
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: "bob".to_string(),
+            token_id: "1".to_string(),
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(
+            Err(ContractError::TokenNotFound {
+                token_id: "1".to_string()
+            }),
+            res
+        );
+
+        for token_id in ["2", "0"] {
+            let info = mock_info("alice", &[]);
+            let exec_msg = ExecuteMsg::TransferNft {
+                recipient: "bob".to_string(),
+                token_id: token_id.to_string(),
+                memo: Some("gg".to_string()),
+            };
+            let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+            let status = ResponseStatus::Success;
+            assert_eq!(
+                ExecuteAnswer::TransferNft { status },
+                from_binary(&res.data.unwrap()).unwrap()
+            );
+        }
+
+        assert!(tokens(deps.as_ref(), "alice", Some("alice")).is_empty());
+        assert_eq!(vec!["0", "2"], tokens(deps.as_ref(), "bob", Some("bob")));
+
+        // the holder of a private map is not shown to anyone
+        let owner_of = |viewer: Option<ViewerInfo>| QueryMsg::OwnerOf {
+            token_id: "0".to_string(),
+            viewer,
+            include_expired: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), owner_of(None));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+        let res = query(deps.as_ref(), mock_env(), owner_of(Some(viewer("alice"))));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+        let res = query(deps.as_ref(), mock_env(), owner_of(Some(viewer("bob")))).unwrap();
+        assert_eq!(
+            QueryAnswer::OwnerOf {
+                owner: Addr::unchecked("bob"),
+                approvals: vec![],
+            },
+            from_binary(&res).unwrap()
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapInfo { id: 0 }).unwrap();
+        let value: MapInfoResponse = from_binary(&res).unwrap();
+        assert_eq!("alice", value.creator.as_str());
+        assert_eq!("bob", value.owner.as_str());
+
+        // the tiles and the right to delete follow the token
+        let res = query(deps.as_ref(), mock_env(), private_metadata(Some("alice")));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+        query(deps.as_ref(), mock_env(), private_metadata(Some("bob"))).unwrap();
+
+        let info = mock_info("alice", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::DeleteMap { id: 0 },
+        );
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let info = mock_info("bob", &[]);
+        let exec_msg = ExecuteMsg::DeleteMap { id: 2 };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        assert_eq!(vec!["0"], tokens(deps.as_ref(), "bob", Some("bob")));
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::NumTokens { viewer: None },
+        )
+        .unwrap();
+        assert_eq!(
+            QueryAnswer::NumTokens { count: 1 },
+            from_binary(&res).unwrap()
+        );
+    }
+
+    #[test]
+    fn approve_and_batch_transfer_nft() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // alice mints private tokens 0, 1 and 2
+        for _ in 0..3 {
+            let info = mock_info("alice", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: Some(true),
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+        for owner in ["alice", "bob", "carol"] {
+            let info = mock_info(owner, &[]);
+            let exec_msg = ExecuteMsg::SetViewingKey {
+                key: format!("{} key", owner),
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+        let transfer = |recipient: &str, token_id: &str| ExecuteMsg::TransferNft {
+            recipient: recipient.to_string(),
+            token_id: token_id.to_string(),
+            memo: None,
+        };
+
+        // bob may move token 0 until the height he was approved for
+        let expires = Expiration::AtHeight(mock_env().block.height + 10);
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::Approve {
+            spender: "bob".to_string(),
+            token_id: "0".to_string(),
+            expires: Some(expires),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let status = ResponseStatus::Success;
+        assert_eq!(
+            ExecuteAnswer::Approve { status },
+            from_binary(&res.data.unwrap()).unwrap()
+        );
+
+        // only the holder is shown the approvals, the spender only the holder
+        let owner_of = |viewer: &str| QueryMsg::OwnerOf {
+            token_id: "0".to_string(),
+            viewer: Some(ViewerInfo {
+                address: viewer.to_string(),
+                viewing_key: format!("{} key", viewer),
+            }),
+            include_expired: None,
+        };
+        let approval = Approval {
+            spender: Addr::unchecked("bob"),
+            expires,
+        };
+        let res = query(deps.as_ref(), mock_env(), owner_of("alice")).unwrap();
+        assert_eq!(
+            QueryAnswer::OwnerOf {
+                owner: Addr::unchecked("alice"),
+                approvals: vec![approval.clone()],
+            },
+            from_binary(&res).unwrap()
+        );
+        let res = query(deps.as_ref(), mock_env(), owner_of("bob")).unwrap();
+        assert_eq!(
+            QueryAnswer::OwnerOf {
+                owner: Addr::unchecked("alice"),
+                approvals: vec![],
+            },
+            from_binary(&res).unwrap()
+        );
+        let res = query(deps.as_ref(), mock_env(), owner_of("carol"));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        // a spender can not approve others, and lapsed approvals do nothing
+        let info = mock_info("bob", &[]);
+        let exec_msg = ExecuteMsg::Approve {
+            spender: "carol".to_string(),
+            token_id: "0".to_string(),
+            expires: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let mut env = mock_env();
+        env.block.height += 10;
+        let info = mock_info("bob", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, transfer("bob", "0"));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let msg = QueryMsg::OwnerOf {
+            token_id: "0".to_string(),
+            viewer: Some(ViewerInfo {
+                address: "alice".to_string(),
+                viewing_key: "alice key".to_string(),
+            }),
+            include_expired: Some(true),
+        };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        assert_eq!(
+            QueryAnswer::OwnerOf {
+                owner: Addr::unchecked("alice"),
+                approvals: vec![approval],
+            },
+            from_binary(&res).unwrap()
+        );
+
+        let info = mock_info("bob", &[]);
+        execute(deps.as_mut(), mock_env(), info, transfer("carol", "0")).unwrap();
+
+        // approvals do not outlive the transfer, nor a revoke
+        let info = mock_info("bob", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, transfer("bob", "0"));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::Approve {
+            spender: "bob".to_string(),
+            token_id: "1".to_string(),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::Revoke {
+            spender: "bob".to_string(),
+            token_id: "1".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let info = mock_info("bob", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, transfer("bob", "1"));
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        // an operator sees and moves all of the holder's tokens, in one batch
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::ApproveAll {
+            operator: "bob".to_string(),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let approved_for_all = |viewing_key: Option<&str>| QueryMsg::ApprovedForAll {
+            owner: "alice".to_string(),
+            viewing_key: viewing_key.map(String::from),
+            include_expired: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), approved_for_all(None)).unwrap();
+        assert_eq!(
+            QueryAnswer::ApprovedForAll { operators: vec![] },
+            from_binary(&res).unwrap()
+        );
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            approved_for_all(Some("alice key")),
+        )
+        .unwrap();
+        assert_eq!(
+            QueryAnswer::ApprovedForAll {
+                operators: vec![Approval {
+                    spender: Addr::unchecked("bob"),
+                    expires: Expiration::Never,
+                }],
+            },
+            from_binary(&res).unwrap()
+        );
+
+        let tokens = |deps: Deps, owner: &str, viewer: &str| -> QueryAnswer {
+            let msg = QueryMsg::Tokens {
+                owner: owner.to_string(),
+                viewer: Some(viewer.to_string()),
+                viewing_key: Some(format!("{} key", viewer)),
+                start_after: None,
+                limit: None,
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(
+            QueryAnswer::TokenList {
+                tokens: vec!["1".to_string(), "2".to_string()]
+            },
+            tokens(deps.as_ref(), "alice", "bob")
+        );
+        assert_eq!(
+            QueryAnswer::TokenList { tokens: vec![] },
+            tokens(deps.as_ref(), "alice", "carol")
+        );
+
+        let info = mock_info("bob", &[]);
+        let exec_msg = ExecuteMsg::BatchTransferNft {
+            transfers: vec![
+                Transfer {
+                    recipient: "bob".to_string(),
+                    token_ids: vec!["1".to_string()],
+                    memo: None,
+                },
+                Transfer {
+                    recipient: "carol".to_string(),
+                    token_ids: vec!["2".to_string()],
+                    memo: None,
+                },
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let status = ResponseStatus::Success;
+        assert_eq!(
+            ExecuteAnswer::BatchTransferNft { status },
+            from_binary(&res.data.unwrap()).unwrap()
+        );
+        assert_eq!(
+            QueryAnswer::TokenList {
+                tokens: vec!["1".to_string()]
+            },
+            tokens(deps.as_ref(), "bob", "bob")
+        );
+        assert_eq!(
+            QueryAnswer::TokenList {
+                tokens: vec!["0".to_string(), "2".to_string()]
+            },
+            tokens(deps.as_ref(), "carol", "carol")
+        );
+
+        // a batch fails as a whole
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::RevokeAll {
+            operator: "bob".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let info = mock_info("bob", &[]);
+        let exec_msg = ExecuteMsg::BatchTransferNft {
+            transfers: vec![Transfer {
+                recipient: "alice".to_string(),
+                token_ids: vec!["1".to_string(), "2".to_string()],
+                memo: None,
+            }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+    }
+
+    #[test]
+    fn send_nft() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // alice mints tokens 0 to 3
+        for _ in 0..4 {
+            let info = mock_info("alice", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: Some(true),
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+        let callback = |contract: &str, code_hash: &str, msg: ReceiverMsg| {
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: contract.to_string(),
+                code_hash: code_hash.to_string(),
+                msg: to_binary(&msg).unwrap(),
+                funds: vec![],
+            })
+        };
+
+        // contracts that did not register get the token without a callback
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::SendNft {
+            contract: "vault".to_string(),
+            receiver_info: None,
+            token_id: "0".to_string(),
+            msg: None,
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        assert!(res.messages.is_empty());
+        let status = ResponseStatus::Success;
+        assert_eq!(
+            ExecuteAnswer::SendNft { status },
+            from_binary(&res.data.unwrap()).unwrap()
+        );
+
+        let info = mock_info("vault", &[]);
+        let exec_msg = ExecuteMsg::RegisterReceiveNft {
+            code_hash: "vault hash".to_string(),
+            also_implements_batch_receive_nft: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        // one `ReceiveNft` per token from a receiver that can not batch
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::BatchSendNft {
+            sends: vec![Send {
+                contract: "vault".to_string(),
+                receiver_info: None,
+                token_ids: vec!["1".to_string(), "2".to_string()],
+                msg: Some(Binary::from(b"stash".to_vec())),
+                memo: None,
+            }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let receive = |token_id: &str| ReceiverMsg::ReceiveNft {
+            sender: Addr::unchecked("alice"),
+            token_id: token_id.to_string(),
+            msg: Some(Binary::from(b"stash".to_vec())),
+        };
+        assert_eq!(
+            vec![
+                callback("vault", "vault hash", receive("1")),
+                callback("vault", "vault hash", receive("2")),
+            ],
+            res.messages
+        );
+
+        // the receiver info given with the send wins over the registration
+        let info = mock_info("vault", &[]);
+        let exec_msg = ExecuteMsg::BatchSendNft {
+            sends: vec![Send {
+                contract: "market".to_string(),
+                receiver_info: Some(ReceiverInfo {
+                    recipient_code_hash: "market hash".to_string(),
+                    also_implements_batch_receive_nft: Some(true),
+                }),
+                token_ids: vec!["1".to_string(), "2".to_string()],
+                msg: None,
+                memo: None,
+            }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let receive = ReceiverMsg::BatchReceiveNft {
+            sender: Addr::unchecked("vault"),
+            from: Addr::unchecked("vault"),
+            token_ids: vec!["1".to_string(), "2".to_string()],
+            msg: None,
+        };
+        assert_eq!(
+            vec![callback("market", "market hash", receive)],
+            res.messages
+        );
+
+        // only the holder and those it approved may send
+        let info = mock_info("bob", &[]);
+        let exec_msg = ExecuteMsg::SendNft {
+            contract: "vault".to_string(),
+            receiver_info: None,
+            token_id: "3".to_string(),
+            msg: None,
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+    }
+
+    #[test]
+    fn migrate_indexes_tokens() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        for (owner, mint) in [
+            ("alice", true),
+            ("alice", false),
+            ("bob", true),
+            ("alice", true),
+        ] {
+            let info = mock_info(owner, &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: Some(false),
+                mint: Some(mint),
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+
+        // storage as written before tokens were indexed
+        TOKEN_COUNT.remove(deps.as_mut().storage);
+        for owner in ["alice", "bob"] {
+            owner_tokens(&Addr::unchecked(owner)).clear(deps.as_mut().storage);
+        }
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::NumTokens { viewer: None },
+        )
+        .unwrap();
+        assert_eq!(
+            QueryAnswer::NumTokens { count: 3 },
+            from_binary(&res).unwrap()
+        );
+        for (owner, expected) in [("alice", vec!["0", "3"]), ("bob", vec!["2"])] {
+            let msg = QueryMsg::Tokens {
+                owner: owner.to_string(),
+                viewer: None,
+                viewing_key: None,
+                start_after: None,
+                limit: None,
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let tokens = expected.into_iter().map(String::from).collect();
+            assert_eq!(
+                QueryAnswer::TokenList { tokens },
+                from_binary(&res).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn query_maps_paginated() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        for _ in 0..12 {
+            let info = mock_info("anyone", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: None,
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+
+        let page = |start_after, limit, order| -> MapsResponse {
+            let msg = QueryMsg::GetMaps {
+                start_after,
+                limit,
+                order,
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+        let indexes =
+            |res: &MapsResponse| -> Vec<u32> { res.maps.iter().map(|m| m.index).collect() };
+
+        // default limit, ascending
+        let res = page(None, None, None);
+        assert_eq!((0..10).collect::<Vec<_>>(), indexes(&res));
+        assert_eq!(Some(9), res.next);
+
+        let res = page(res.next, None, None);
+        assert_eq!(vec![10, 11], indexes(&res));
+        assert_eq!(None, res.next);
+
+        // an oversized limit is clamped rather than rejected
+        let res = page(None, Some(1000), None);
+        assert_eq!(12, res.maps.len());
+        assert_eq!(None, res.next);
+
+        // descending
+        let res = page(None, Some(5), Some(Order::Descending));
+        assert_eq!(vec![11, 10, 9, 8, 7], indexes(&res));
+        assert_eq!(Some(7), res.next);
+
+        let res = page(Some(2), Some(5), Some(Order::Descending));
+        assert_eq!(vec![1, 0], indexes(&res));
+        assert_eq!(None, res.next);
+
+        let res = page(Some(11), None, None);
+        assert!(res.maps.is_empty());
+        assert_eq!(None, res.next);
+    }
+
+    #[test]
+    fn query_maps_scan_is_bounded() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // a run of deleted maps longer than one scan, then one that is kept
+        let scan = MAX_PAGE_SCAN;
+        for id in 0..=scan {
+            let info = mock_info("anyone", &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: None,
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+            if id < scan {
+                let info = mock_info("anyone", &[]);
+                let exec_msg = ExecuteMsg::DeleteMap { id };
+                execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+            }
+        }
+
+        let page = |start_after| -> MapsResponse {
+            let msg = QueryMsg::GetMaps {
+                start_after,
+                limit: None,
+                order: None,
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+
+        let res = page(None);
+        assert!(res.maps.is_empty());
+        assert_eq!(Some(scan - 1), res.next);
+
+        let res = page(res.next);
+        assert_eq!(
+            vec![scan],
+            res.maps.iter().map(|m| m.index).collect::<Vec<_>>()
+        );
+        assert_eq!(None, res.next);
+    }
+
+    #[test]
+    fn query_maps_by_owner() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // alice generates maps 0, 2, 4 and 6, bob the odd ones
+        for id in 0..8 {
+            let info = mock_info(if id % 2 == 0 { "alice" } else { "bob" }, &[]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: None,
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+
+        let page = |owner: &str, start_after, limit, order| -> MapsResponse {
+            let msg = QueryMsg::GetMapsByOwner {
+                owner: owner.to_string(),
+                start_after,
+                limit,
+                order,
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+        let indexes =
+            |res: &MapsResponse| -> Vec<u32> { res.maps.iter().map(|m| m.index).collect() };
+
+        let res = page("alice", None, Some(3), None);
+        assert_eq!(vec![0, 2, 4], indexes(&res));
+        assert_eq!(Some(4), res.next);
+
+        let res = page("alice", res.next, Some(3), None);
+        assert_eq!(vec![6], indexes(&res));
+        assert_eq!(None, res.next);
+
+        let res = page("bob", None, Some(2), Some(Order::Descending));
+        assert_eq!(vec![7, 5], indexes(&res));
+        assert_eq!(Some(5), res.next);
+
+        let res = page("bob", res.next, Some(2), Some(Order::Descending));
+        assert_eq!(vec![3, 1], indexes(&res));
+        assert_eq!(None, res.next);
+
+        // the maps match the ones in the global list
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMap {
+                index: 3,
+                encoding: None,
+            },
+        )
+        .unwrap();
+        let value: MapResponse = from_binary(&res).unwrap();
+        assert_eq!(value, page("bob", Some(1), Some(1), None).maps[0]);
+
+        let res = page("carol", None, None, None);
+        assert!(res.maps.is_empty());
+        assert_eq!(None, res.next);
+    }
+
+    #[test]
+    fn query_map_info() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.height = 4242;
+        let info = mock_info("alice", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, exec_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapInfo { id: 0 }).unwrap();
+        let value: MapInfoResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.id);
+        assert_eq!("alice", value.creator.as_str());
+        assert_eq!(4242, value.height);
+        assert_eq!(env.block.time, value.time);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapInfo { id: 1 });
+        assert_eq!(Err(ContractError::MapNotFound { index: 1 }), res);
+    }
+
+    #[test]
+    fn transfer_ownership() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::TransferOwnership {
+            new_owner: "alice".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let exec_msg = ExecuteMsg::TransferOwnership {
+            new_owner: "alice".to_string(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+
+        // the current owner stays in charge until the transfer is accepted
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!("creator", value.owner.as_str());
+        assert_eq!(Some(Addr::unchecked("alice")), value.pending_owner);
+
+        let exec_msg = ExecuteMsg::AcceptOwnership {};
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let exec_msg = ExecuteMsg::AcceptOwnership {};
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!("alice", value.owner.as_str());
+        assert_eq!(None, value.pending_owner);
+
+        let exec_msg = ExecuteMsg::Reset { count: 5 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        );
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+        let exec_msg = ExecuteMsg::Reset { count: 5 };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg).unwrap();
+    }
+
+    #[test]
+    fn manage_admins() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        // only the owner manages admins
+        let exec_msg = ExecuteMsg::AddAdmin {
+            admin: "alice".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        for _ in 0..2 {
+            let exec_msg = ExecuteMsg::AddAdmin {
+                admin: "alice".to_string(),
+            };
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                exec_msg,
+            )
+            .unwrap();
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![Addr::unchecked("alice")], value.admins);
+
+        // admins can reset and clear, but not manage other admins
+        let exec_msg = ExecuteMsg::Reset { count: 5 };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Clear {};
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::AddAdmin {
+            admin: "bob".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let exec_msg = ExecuteMsg::RemoveAdmin {
+            admin: "alice".to_string(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+
+        let exec_msg = ExecuteMsg::Clear {};
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+    }
+
+    #[test]
+    fn pause() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let generate = || ExecuteMsg::Generate {
+            size: Some(Size::new(20, 20)),
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            generate(),
+        )
+        .unwrap();
+
+        let exec_msg = ExecuteMsg::SetPaused { paused: true };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let exec_msg = ExecuteMsg::SetPaused { paused: true };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert!(value.paused);
+
+        for exec_msg in [
+            generate(),
+            ExecuteMsg::Increment {},
+            ExecuteMsg::DeleteMap { id: 0 },
+        ] {
+            let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+            assert_eq!(Err(ContractError::Paused {}), res);
+        }
+
+        // queries and key management keep working
         let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
         let value: MapCountResponse = from_binary(&res).unwrap();
         assert_eq!(1, value.count);
+        let exec_msg = ExecuteMsg::SetViewingKey {
+            key: "key".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::SetPaused { paused: false };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            generate(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn generation_fee() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: Some(coin(100, "uscrt")),
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFee {}).unwrap();
+        let value: FeeResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(coin(100, "uscrt")), value.fee);
+
+        let generate = || ExecuteMsg::Generate {
+            size: Some(Size::new(20, 20)),
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+
+        for funds in [vec![], vec![coin(40, "uscrt"), coin(500, "uatom")]] {
+            let info = mock_info("alice", &funds);
+            let res = execute(deps.as_mut(), mock_env(), info, generate());
+            assert_eq!(
+                Err(ContractError::InsufficientFee {
+                    required: coin(100, "uscrt"),
+                    paid: Uint128::new(if funds.is_empty() { 0 } else { 40 }),
+                }),
+                res
+            );
+        }
+
+        let info = mock_info("alice", &[coin(100, "uscrt")]);
+        let res = execute(deps.as_mut(), mock_env(), info, generate()).unwrap();
+        assert!(res.messages.is_empty());
+
+        // anything paid on top of the fee is sent back
+        let info = mock_info("alice", &[coin(150, "uscrt"), coin(5, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, generate()).unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: vec![coin(50, "uscrt"), coin(5, "uatom")],
+            })],
+            res.messages
+        );
+
+        let exec_msg = ExecuteMsg::SetFee { fee: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let exec_msg = ExecuteMsg::SetFee { fee: None };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            generate(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn withdraw_fees() {
+        // funds sent to the contract other than fees are never withdrawn
+        let mut deps = mock_dependencies_with_balance(&[coin(1000, "uscrt")]);
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: Some(coin(100, "uscrt")),
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let withdraw = |deps: DepsMut, recipient: Option<&str>| {
+            let exec_msg = ExecuteMsg::WithdrawFees {
+                recipient: recipient.map(str::to_string),
+            };
+            execute(deps, mock_env(), mock_info("creator", &[]), exec_msg)
+                .unwrap()
+                .messages
+        };
+        assert!(withdraw(deps.as_mut(), None).is_empty());
+
+        // the overpayment is refunded and not collected
+        for paid in [100, 150] {
+            let info = mock_info("alice", &[coin(paid, "uscrt")]);
+            let exec_msg = ExecuteMsg::Generate {
+                size: Some(Size::new(20, 20)),
+                min_room_size: None,
+                max_room_size: None,
+                private: None,
+                mint: None,
+                generator: None,
+            };
+            execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        }
+
+        let exec_msg = ExecuteMsg::WithdrawFees { recipient: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(200, "uscrt")],
+            })],
+            withdraw(deps.as_mut(), Some("treasury"))
+        );
+
+        // and nothing is withdrawn twice
+        assert!(withdraw(deps.as_mut(), None).is_empty());
+
+        let info = mock_info("bob", &[coin(100, "uscrt")]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: Some(Size::new(20, 20)),
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: vec![coin(100, "uscrt")],
+            })],
+            withdraw(deps.as_mut(), None)
+        );
+    }
+
+    #[test]
+    fn generation_limits() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let limits = Limits {
+            max_maps_per_owner: Some(2),
+            min_blocks_between_generations: Some(10),
+        };
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: Some(limits),
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetLimits {}).unwrap();
+        let value: LimitsResponse = from_binary(&res).unwrap();
+        assert_eq!(limits, value.limits);
+
+        let start = mock_env().block.height;
+        let at = |height: u64| {
+            let mut env = mock_env();
+            env.block.height = height;
+            env
+        };
+        let generate = || ExecuteMsg::Generate {
+            size: Some(Size::new(20, 20)),
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        let quota = |deps: Deps, env: Env| -> QuotaResponse {
+            let msg = QueryMsg::GetQuota {
+                address: "alice".to_string(),
+            };
+            from_binary(&query(deps, env, msg).unwrap()).unwrap()
+        };
+
+        let value = quota(deps.as_ref(), at(start));
+        assert_eq!(
+            (0, Some(2), start),
+            (value.maps, value.remaining_maps, value.next_height)
+        );
+
+        execute(
+            deps.as_mut(),
+            at(start),
+            mock_info("alice", &[]),
+            generate(),
+        )
+        .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            at(start + 5),
+            mock_info("alice", &[]),
+            generate(),
+        );
+        assert_eq!(
+            Err(ContractError::GenerateTooSoon {
+                next_height: start + 10
+            }),
+            res
+        );
+        let value = quota(deps.as_ref(), at(start + 5));
+        assert_eq!(
+            (1, Some(1), start + 10),
+            (value.maps, value.remaining_maps, value.next_height)
+        );
+
+        // the wait is per address
+        execute(
+            deps.as_mut(),
+            at(start + 5),
+            mock_info("bob", &[]),
+            generate(),
+        )
+        .unwrap();
+
+        execute(
+            deps.as_mut(),
+            at(start + 10),
+            mock_info("alice", &[]),
+            generate(),
+        )
+        .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            at(start + 20),
+            mock_info("alice", &[]),
+            generate(),
+        );
+        assert_eq!(Err(ContractError::MapQuotaExceeded { max: 2 }), res);
+        let value = quota(deps.as_ref(), at(start + 20));
+        assert_eq!(Some(0), value.remaining_maps);
+
+        // deleting a map frees up its slot
+        let exec_msg = ExecuteMsg::DeleteMap { id: 0 };
+        execute(
+            deps.as_mut(),
+            at(start + 20),
+            mock_info("alice", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            at(start + 20),
+            mock_info("alice", &[]),
+            generate(),
+        )
+        .unwrap();
+
+        let exec_msg = ExecuteMsg::SetLimits {
+            limits: Limits::default(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), exec_msg);
+        assert_eq!(Err(ContractError::Unauthorized {}), res);
+
+        let exec_msg = ExecuteMsg::SetLimits {
+            limits: Limits::default(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            at(start + 20),
+            mock_info("alice", &[]),
+            generate(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn migrate_legacy_state() {
+        let mut deps = mock_dependencies();
+
+        // storage as written by the contract before maps had their own keymap
+        let legacy = LegacyState {
+            count: 3,
+            owner: Addr::unchecked("creator"),
+            maps: vec!["11\n01\n11\n".to_string(), "10\n11\n".to_string()],
+        };
+        singleton(deps.as_mut().storage, CONFIG_KEY)
+            .save(&legacy)
+            .unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(CONTRACT_VERSION_STR, res.attributes[0].value);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: CountResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.count);
+
+        let msg = QueryMsg::GetMaps {
+            start_after: None,
+            limit: None,
+            order: None,
+        };
+        let value: MapsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        let maps: Vec<_> = value.maps.iter().map(|m| m.map.as_str()).collect();
+        assert_eq!(vec!["101\n111\n", "11\n01\n"], maps);
+
+        assert_eq!(
+            current_version(),
+            CONTRACT_VERSION.load(deps.as_ref().storage).unwrap()
+        );
+
+        // the migrated contract keeps working, and migrating again changes nothing
+        let info = mock_info("anyone", &[]);
+        let exec_msg = ExecuteMsg::Generate {
+            size: None,
+            min_room_size: None,
+            max_room_size: None,
+            private: None,
+            mint: None,
+            generator: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        assert_eq!("2", res.attributes[0].value);
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMapCount {}).unwrap();
+        let value: MapCountResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.count);
+    }
+
+    #[test]
+    fn migrate_rejects_unknown_versions() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+        for (contract, version) in [
+            ("other_contract", CONTRACT_VERSION_STR),
+            (CONTRACT_NAME, "99.0.0"),
+            (CONTRACT_NAME, "latest"),
+        ] {
+            let stored = ContractVersion {
+                contract: contract.to_string(),
+                version: version.to_string(),
+            };
+            CONTRACT_VERSION
+                .save(deps.as_mut().storage, &stored)
+                .unwrap();
+
+            let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+            assert_eq!(
+                Err(ContractError::UnsupportedMigration {
+                    contract: contract.to_string(),
+                    version: version.to_string(),
+                }),
+                res
+            );
+        }
     }
 
     #[test]
@@ -263,7 +4749,12 @@ mod tests {
                 amount: Uint128::new(2),
             }],
         );
-        let init_msg = InstantiateMsg { count: 17 };
+        let init_msg = InstantiateMsg {
+            count: 17,
+            storage_mode: None,
+            fee: None,
+            limits: None,
+        };
 
         let _res = instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
 
@@ -280,7 +4771,7 @@ mod tests {
         let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
 
         match res {
-            Err(StdError::GenericErr { .. }) => {}
+            Err(ContractError::Unauthorized {}) => {}
             _ => panic!("Must return unauthorized error"),
         }
 
@@ -0,0 +1,68 @@
+use cosmwasm_std::{Coin, StdError, Uint128};
+use thiserror::Error;
+
+use crate::bsp::CodecError;
+use crate::generator::GeneratorError;
+use crate::wfc::WfcError;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("Contract is paused")]
+    Paused {},
+
+    #[error("Generating a map costs {required}, but only {paid} was paid")]
+    InsufficientFee { required: Coin, paid: Uint128 },
+
+    #[error("Map quota of {max} per owner reached")]
+    MapQuotaExceeded { max: u32 },
+
+    #[error("Generating again is allowed from block {next_height}")]
+    GenerateTooSoon { next_height: u64 },
+
+    #[error("Map {index} not found")]
+    MapNotFound { index: u32 },
+
+    #[error("Invalid map parameters: {0}")]
+    InvalidMapParameters(GeneratorError),
+
+    #[error("Wave function collapse ran into a contradiction on every attempt, {attempts} in all")]
+    Contradiction { attempts: u32 },
+
+    #[error("Invalid tileset: {0}")]
+    InvalidTileset(WfcError),
+
+    #[error("Tileset {name} not found")]
+    TilesetNotFound { name: String },
+
+    #[error("Maps stored as a seed may have at most {max_cells} cells")]
+    SeedMapTooLarge { max_cells: u64 },
+
+    #[error("Block randomness is not available")]
+    MissingRandomness {},
+
+    #[error("Token {token_id} not found")]
+    TokenNotFound { token_id: String },
+
+    #[error("Cannot migrate from {contract} {version}")]
+    UnsupportedMigration { contract: String, version: String },
+
+    #[error("Stored map is corrupt: {0}")]
+    InvalidTileEncoding(#[from] CodecError),
+}
+
+impl From<GeneratorError> for ContractError {
+    fn from(err: GeneratorError) -> Self {
+        match err {
+            GeneratorError::Wfc(WfcError::Contradiction { attempts }) => {
+                ContractError::Contradiction { attempts }
+            }
+            err => ContractError::InvalidMapParameters(err),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::bsp::{BSPMap, MapError, MapParams, Rectangle, Size, TileGrid};
+use crate::cave::{self, CaveError, CaveParams};
+use crate::noise::{self, NoiseError, NoiseParams};
+use crate::rand::MapRng;
+use crate::state::TILESET_CONTENTS;
+use crate::walk::{self, WalkError, WalkParams};
+use crate::wfc::{self, WfcError, WfcParams};
+use cosmwasm_std::Storage;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// the generator a map is built with, along with everything besides the seed
+// that the map's layout depends on
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorParams {
+    Bsp(MapParams),
+    Cave(CaveParams),
+    Walk(WalkParams),
+    Wfc(WfcParams),
+    Noise(NoiseParams),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GeneratorError {
+    #[error(transparent)]
+    Bsp(#[from] MapError),
+    #[error(transparent)]
+    Cave(#[from] CaveError),
+    #[error(transparent)]
+    Walk(#[from] WalkError),
+    #[error(transparent)]
+    Wfc(#[from] WfcError),
+    #[error(transparent)]
+    Noise(#[from] NoiseError),
+}
+
+// what every generator produces; only BSP maps have rooms and corridors
+pub struct GeneratedMap {
+    pub grid: TileGrid,
+    pub rooms: Vec<Rectangle>,
+    pub corridors: Vec<Rectangle>,
+}
+
+impl GeneratorParams {
+    #[must_use]
+    pub fn size(&self) -> Size {
+        match self {
+            GeneratorParams::Bsp(params) => params.size,
+            GeneratorParams::Cave(params) => params.size,
+            GeneratorParams::Walk(params) => params.size,
+            GeneratorParams::Wfc(params) => params.size,
+            GeneratorParams::Noise(params) => params.size,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), GeneratorError> {
+        match self {
+            GeneratorParams::Bsp(params) => params.validate()?,
+            GeneratorParams::Cave(params) => params.validate()?,
+            GeneratorParams::Walk(params) => params.validate()?,
+            GeneratorParams::Wfc(params) => params.validate()?,
+            GeneratorParams::Noise(params) => params.validate()?,
+        }
+        Ok(())
+    }
+
+    // `storage` holds the tilesets wave function collapse maps refer to
+    pub fn generate(
+        &self,
+        storage: &dyn Storage,
+        seed: impl MapRng,
+    ) -> Result<GeneratedMap, GeneratorError> {
+        match self {
+            GeneratorParams::Bsp(params) => {
+                let map = BSPMap::from_params(params, seed)?;
+                Ok(GeneratedMap {
+                    grid: map.grid(),
+                    rooms: map.rooms().to_vec(),
+                    corridors: map.corridors().to_vec(),
+                })
+            }
+            GeneratorParams::Cave(params) => Ok(GeneratedMap {
+                grid: cave::from_params(params, seed)?,
+                rooms: vec![],
+                corridors: vec![],
+            }),
+            GeneratorParams::Walk(params) => Ok(GeneratedMap {
+                grid: walk::from_params(params, seed)?,
+                rooms: vec![],
+                corridors: vec![],
+            }),
+            GeneratorParams::Wfc(params) => {
+                let tileset = TILESET_CONTENTS
+                    .get(storage, &params.tileset)
+                    .ok_or_else(|| WfcError::UnknownTileset {
+                        hash: params.tileset.clone(),
+                    })?;
+                Ok(GeneratedMap {
+                    grid: wfc::from_params(params, &tileset, seed)?,
+                    rooms: vec![],
+                    corridors: vec![],
+                })
+            }
+            GeneratorParams::Noise(params) => Ok(GeneratedMap {
+                grid: noise::from_params(params, seed)?,
+                rooms: vec![],
+                corridors: vec![],
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsp::Tile;
+    use crate::rand::MersenneTwister;
+    use crate::state::store_tileset;
+    use crate::wfc::{Tileset, TilesetTile};
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::Binary;
+
+    // storage with a tileset for wave function collapse, and the tileset's hash
+    fn storage() -> (MockStorage, Binary) {
+        let tile = |tile| TilesetTile { tile, weight: 1 };
+        let pairs = vec![(0, 0), (0, 1), (1, 0), (1, 1)];
+        let tileset = Tileset {
+            tiles: vec![tile(Tile::Floor), tile(Tile::Wall)],
+            horizontal: pairs.clone(),
+            vertical: pairs,
+        };
+        let mut storage = MockStorage::new();
+        let hash = store_tileset(&mut storage, &tileset).unwrap();
+        (storage, hash)
+    }
+
+    fn generators(size: Size, tileset: Binary) -> Vec<GeneratorParams> {
+        vec![
+            GeneratorParams::Bsp(MapParams {
+                size,
+                min_room_size: Size::new(6, 6),
+                max_room_size: Size::new(10, 10),
+            }),
+            GeneratorParams::Cave(CaveParams {
+                size,
+                fill_percent: 45,
+                iterations: 4,
+                birth_limit: 5,
+                survival_limit: 4,
+            }),
+            GeneratorParams::Walk(WalkParams {
+                size,
+                floor_percent: 40,
+                walkers: 4,
+                bias: None,
+            }),
+            GeneratorParams::Wfc(WfcParams { size, tileset }),
+            GeneratorParams::Noise(NoiseParams {
+                size,
+                octaves: 4,
+                scale: 16,
+                sea_level: 40,
+                beach_level: 45,
+                mountain_level: 60,
+                forest_moisture: 50,
+            }),
+        ]
+    }
+
+    #[test]
+    fn generators_are_deterministic() {
+        let (storage, tileset) = storage();
+        for params in generators(Size::new(40, 30), tileset) {
+            let first = params.generate(&storage, MersenneTwister::new(7)).unwrap();
+            let second = params.generate(&storage, MersenneTwister::new(7)).unwrap();
+            let other = params.generate(&storage, MersenneTwister::new(8)).unwrap();
+            assert_eq!(first.grid, second.grid, "{params:?}");
+            assert_ne!(first.grid, other.grid, "{params:?}");
+            assert_eq!(params.size(), first.grid.size());
+        }
+    }
+
+    #[test]
+    fn generators_cap_map_size() {
+        for size in [
+            Size::new(129, 30),
+            Size::new(40, 129),
+            Size::new(u32::MAX, u32::MAX),
+        ] {
+            for params in generators(size, Binary::default()) {
+                let err = match params {
+                    GeneratorParams::Bsp(_) => MapError::MapTooLarge.into(),
+                    GeneratorParams::Cave(_) => CaveError::TooLarge.into(),
+                    GeneratorParams::Walk(_) => WalkError::TooLarge.into(),
+                    GeneratorParams::Wfc(_) => WfcError::TooLarge.into(),
+                    GeneratorParams::Noise(_) => NoiseError::TooLarge.into(),
+                };
+                assert_eq!(Err(err), params.validate(), "{params:?}");
+            }
+        }
+    }
+}
@@ -1,5 +1,11 @@
 pub mod bsp;
+pub mod cave;
 pub mod contract;
+pub mod error;
+pub mod generator;
 pub mod msg;
+pub mod noise;
 pub mod rand;
 pub mod state;
+pub mod walk;
+pub mod wfc;
@@ -1,18 +1,240 @@
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp};
 use schemars::JsonSchema;
+use secret_toolkit_permit::Permit;
 use serde::{Deserialize, Serialize};
 
+use crate::bsp::{MapData, Size};
+use crate::rand::GeneratorVersion;
+use crate::state::{Approval, Expiration, Limits, StorageMode};
+use crate::walk::WalkBias;
+use crate::wfc::Tileset;
+
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub count: i32,
+    pub storage_mode: Option<StorageMode>,
+    pub fee: Option<Coin>,
+    pub limits: Option<Limits>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Increment {},
-    Reset { count: i32 },
-    Generate {},
+    Reset {
+        count: i32,
+    },
+    Generate {
+        size: Option<Size>,
+        min_room_size: Option<Size>,
+        max_room_size: Option<Size>,
+        private: Option<bool>,
+        // mint the map as a SNIP-721 token owned by the sender
+        mint: Option<bool>,
+        // BSP rooms and corridors when unset
+        generator: Option<GeneratorKind>,
+    },
+    // removes every map that was not minted, without reusing their ids
     Clear {},
+    DeleteMap {
+        id: u32,
+    },
+    CreateViewingKey {
+        entropy: String,
+    },
+    SetViewingKey {
+        key: String,
+    },
+    RevokePermit {
+        permit_name: String,
+    },
+    // let `viewer` read all of the sender's private maps
+    AddViewer {
+        viewer: String,
+    },
+    RemoveViewer {
+        viewer: String,
+    },
+    // proposes a new owner, who takes over with `AcceptOwnership`
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+    AddAdmin {
+        admin: String,
+    },
+    RemoveAdmin {
+        admin: String,
+    },
+    // halts generation and the other map changes, see `execute`
+    SetPaused {
+        paused: bool,
+    },
+    // `None` makes generation free
+    SetFee {
+        fee: Option<Coin>,
+    },
+    SetLimits {
+        limits: Limits,
+    },
+    // sends the collected fees to `recipient`, the owner by default
+    WithdrawFees {
+        recipient: Option<String>,
+    },
+    // adds or replaces a tileset for `GeneratorKind::Wfc`; tilesets are kept by
+    // hash, so maps already generated from the old one still replay
+    SetTileset {
+        name: String,
+        tileset: Tileset,
+    },
+    RemoveTileset {
+        name: String,
+    },
+    // SNIP-721, answered with `ExecuteAnswer`; the holder, an approved spender
+    // or an operator of the holder may transfer or send a token
+    TransferNft {
+        recipient: String,
+        token_id: String,
+        memo: Option<String>,
+    },
+    BatchTransferNft {
+        transfers: Vec<Transfer>,
+    },
+    // transfers the token to `contract` and calls its `ReceiveNft` when it
+    // registered with `RegisterReceiveNft` or `receiver_info` is given
+    SendNft {
+        contract: String,
+        receiver_info: Option<ReceiverInfo>,
+        token_id: String,
+        msg: Option<Binary>,
+        memo: Option<String>,
+    },
+    BatchSendNft {
+        sends: Vec<Send>,
+    },
+    // lets `spender` transfer the token, until `expires` or it changes hands
+    Approve {
+        spender: String,
+        token_id: String,
+        expires: Option<Expiration>,
+    },
+    Revoke {
+        spender: String,
+        token_id: String,
+    },
+    // lets `operator` transfer and approve all of the sender's tokens
+    ApproveAll {
+        operator: String,
+        expires: Option<Expiration>,
+    },
+    RevokeAll {
+        operator: String,
+    },
+    RegisterReceiveNft {
+        code_hash: String,
+        also_implements_batch_receive_nft: Option<bool>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct Transfer {
+    pub recipient: String,
+    pub token_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct Send {
+    pub contract: String,
+    pub receiver_info: Option<ReceiverInfo>,
+    pub token_ids: Vec<String>,
+    pub msg: Option<Binary>,
+    pub memo: Option<String>,
+}
+
+// the receiving contract's code hash, for contracts that did not register
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct ReceiverInfo {
+    pub recipient_code_hash: String,
+    pub also_implements_batch_receive_nft: Option<bool>,
+}
+
+// what `SendNft` and `BatchSendNft` call on the receiving contract
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiverMsg {
+    // `sender` is the token's previous holder
+    ReceiveNft {
+        sender: Addr,
+        token_id: String,
+        msg: Option<Binary>,
+    },
+    BatchReceiveNft {
+        sender: Addr,
+        from: Addr,
+        token_ids: Vec<String>,
+        msg: Option<Binary>,
+    },
+}
+
+// execute results in the SNIP-721 layout, set as the response data
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteAnswer {
+    ViewingKey { key: String },
+    TransferNft { status: ResponseStatus },
+    BatchTransferNft { status: ResponseStatus },
+    SendNft { status: ResponseStatus },
+    BatchSendNft { status: ResponseStatus },
+    Approve { status: ResponseStatus },
+    Revoke { status: ResponseStatus },
+    ApproveAll { status: ResponseStatus },
+    RevokeAll { status: ResponseStatus },
+    RegisterReceiveNft { status: ResponseStatus },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    Success,
+    Failure,
+}
+
+// the generator for `Generate`, unset fields fall back to the defaults in `contract`
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorKind {
+    // rooms and corridors, sized by `min_room_size` and `max_room_size`
+    Bsp {},
+    // organic caves from a cellular automaton
+    Cave {
+        fill_percent: Option<u32>,
+        iterations: Option<u32>,
+        birth_limit: Option<u32>,
+        survival_limit: Option<u32>,
+    },
+    // drunkard's walk, walkers carve floor out of solid wall
+    Walk {
+        floor_percent: Option<u32>,
+        walkers: Option<u32>,
+        bias: Option<WalkBias>,
+    },
+    // wave function collapse over a tileset uploaded with `SetTileset`
+    Wfc {
+        tileset: String,
+    },
+    // overworld terrain, biomes picked from noise elevation and moisture
+    Noise {
+        octaves: Option<u32>,
+        scale: Option<u32>,
+        sea_level: Option<u32>,
+        beach_level: Option<u32>,
+        mountain_level: Option<u32>,
+        forest_moisture: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
@@ -20,9 +242,146 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     // GetCount returns the current count as a json-encoded number
     GetCount {},
-    GetMaps {},
-    GetMap { index: u32 },
+    GetConfig {},
+    GetFee {},
+    GetLimits {},
+    GetTileset {
+        name: String,
+    },
+    // any tileset ever uploaded, by the hash maps refer to it with
+    GetTilesetByHash {
+        hash: Binary,
+    },
+    // how many more maps `address` may generate, and from which block
+    GetQuota {
+        address: String,
+    },
+    GetMaps {
+        start_after: Option<u32>,
+        limit: Option<u32>,
+        order: Option<Order>,
+    },
+    GetMap {
+        index: u32,
+        encoding: Option<MapEncoding>,
+    },
     GetMapCount {},
+    GetMapsByOwner {
+        owner: String,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+        order: Option<Order>,
+    },
+    GetMapInfo {
+        id: u32,
+    },
+    // the authenticated queries below also answer for private maps `address` may view
+    GetPrivateMap {
+        index: u32,
+        encoding: Option<MapEncoding>,
+        address: String,
+        key: String,
+    },
+    GetPrivateMapsByOwner {
+        owner: String,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+        order: Option<Order>,
+        address: String,
+        key: String,
+    },
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
+    // SNIP-721, answered with `QueryAnswer`; without a viewer only public maps'
+    // tokens are shown
+    ContractInfo {},
+    NumTokens {
+        viewer: Option<ViewerInfo>,
+    },
+    // `viewing_key` belongs to `viewer`, or to `owner` when there is no viewer
+    Tokens {
+        owner: String,
+        viewer: Option<String>,
+        viewing_key: Option<String>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    OwnerOf {
+        token_id: String,
+        viewer: Option<ViewerInfo>,
+        include_expired: Option<bool>,
+    },
+    NftInfo {
+        token_id: String,
+    },
+    PrivateMetadata {
+        token_id: String,
+        viewer: Option<ViewerInfo>,
+    },
+    // empty without the owner's viewing key
+    ApprovedForAll {
+        owner: String,
+        viewing_key: Option<String>,
+        include_expired: Option<bool>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct ViewerInfo {
+    pub address: String,
+    pub viewing_key: String,
+}
+
+// queries authenticated by a SNIP-24 permit with the `owner` permission
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    GetMap {
+        index: u32,
+        encoding: Option<MapEncoding>,
+    },
+    GetMapsByOwner {
+        owner: String,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+        order: Option<Order>,
+    },
+    PrivateMetadata {
+        token_id: String,
+    },
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    ApprovedForAll {
+        owner: String,
+        include_expired: Option<bool>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    Ascending,
+    Descending,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MapEncoding {
+    // rendered text, answered with `MapResponse`
+    Text,
+    // tiles and geometry, answered with `MapDataResponse`
+    Structured,
+    // the bit-packed `TileGrid` encoding, answered with `CompactMapResponse`
+    Compact,
 }
 
 // We define a custom struct for each query response
@@ -31,17 +390,120 @@ pub struct CountResponse {
     pub count: i32,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub owner: Addr,
+    pub pending_owner: Option<Addr>,
+    pub admins: Vec<Addr>,
+    pub storage_mode: StorageMode,
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct FeeResponse {
+    pub fee: Option<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct LimitsResponse {
+    pub limits: Limits,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct TilesetResponse {
+    pub tileset: Tileset,
+    pub hash: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct QuotaResponse {
+    pub maps: u32,
+    // `None` when there is no per-owner limit
+    pub remaining_maps: Option<u32>,
+    pub next_height: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 pub struct MapsResponse {
-    pub maps: Vec<String>,
+    pub maps: Vec<MapResponse>,
+    // pass as `start_after` to fetch the next page, `None` once the last page is
+    // reached; pages may come back short, or empty, before that
+    pub next: Option<u32>,
 }
+
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 pub struct MapResponse {
     pub index: u32,
     pub map: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct MapDataResponse {
+    pub index: u32,
+    pub data: MapData,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct CompactMapResponse {
+    pub index: u32,
+    pub map: Binary,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 pub struct MapCountResponse {
+    // maps that are still around, deleted and cleared ones are not counted
     pub count: usize,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct MapInfoResponse {
+    pub id: u32,
+    pub creator: Addr,
+    pub height: u64,
+    pub time: Timestamp,
+    pub private: bool,
+    pub owner: Addr,
+    pub minted: bool,
+}
+
+// SNIP-721 query results, wrapped in the name of the answer as the standard has them
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAnswer {
+    ContractInfo {
+        name: String,
+        symbol: String,
+    },
+    NumTokens {
+        count: u32,
+    },
+    TokenList {
+        tokens: Vec<String>,
+    },
+    // the approvals are only listed for the holder
+    OwnerOf {
+        owner: Addr,
+        approvals: Vec<Approval>,
+    },
+    NftInfo {
+        token_uri: Option<String>,
+        extension: MapMetadata,
+    },
+    // the full tile layout
+    PrivateMetadata {
+        token_uri: Option<String>,
+        extension: MapData,
+    },
+    ApprovedForAll {
+        operators: Vec<Approval>,
+    },
+}
+
+// public token metadata, what the map was generated from but not its tiles
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct MapMetadata {
+    pub size: Size,
+    // left out for private maps, whose tiles it would give away
+    pub seed: Option<Binary>,
+    pub generator_version: Option<GeneratorVersion>,
+}
@@ -0,0 +1,337 @@
+use crate::bsp::{Point, Size, Tile, TileGrid, MAX_MAP_SIZE};
+use crate::rand::MapRng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// levels and moisture are percentages of the noise range; half of the cells
+// lie within 37 to 62, a tenth below 27 and a tenth from 73 on
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+pub struct NoiseParams {
+    pub size: Size,
+    // layers of noise, each at half the scale and half the weight of the one before
+    pub octaves: u32,
+    // cells between the lattice points of the first octave
+    pub scale: u32,
+    // elevation below this is water
+    pub sea_level: u32,
+    // elevation below this but above the sea is sand
+    pub beach_level: u32,
+    // elevation from this on is mountain
+    pub mountain_level: u32,
+    // land in between is forest from this moisture on, grass below it
+    pub forest_moisture: u32,
+}
+
+pub const MAX_OCTAVES: u32 = 6;
+pub const MAX_NOISE_SCALE: u32 = 256;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum NoiseError {
+    #[error("Size of a terrain map must not be empty.")]
+    TooSmall,
+    #[error(
+        "Size of a terrain map needs to be less than or equal {}",
+        MAX_MAP_SIZE
+    )]
+    TooLarge,
+    #[error("Number of noise octaves must be between 1 and {}.", MAX_OCTAVES)]
+    InvalidOctaves,
+    #[error("Noise scale must be between 1 and {}.", MAX_NOISE_SCALE)]
+    InvalidNoiseScale,
+    #[error("Biome thresholds must be percentages, with sea, beach and mountain levels in rising order.")]
+    InvalidBiomeThresholds,
+}
+
+// noise is computed in fixed point, as fractions of `ONE`, so every node
+// produces the same map
+const ONE: i64 = 1 << 16;
+
+impl NoiseParams {
+    pub fn validate(&self) -> Result<(), NoiseError> {
+        if self.size.width() == 0 || self.size.height() == 0 {
+            return Err(NoiseError::TooSmall);
+        }
+        if !self.size.fits_within(MAX_MAP_SIZE) {
+            return Err(NoiseError::TooLarge);
+        }
+        if self.octaves == 0 || self.octaves > MAX_OCTAVES {
+            return Err(NoiseError::InvalidOctaves);
+        }
+        if self.scale == 0 || self.scale > MAX_NOISE_SCALE {
+            return Err(NoiseError::InvalidNoiseScale);
+        }
+        if self.sea_level > self.beach_level
+            || self.beach_level > self.mountain_level
+            || self.mountain_level > 100
+            || self.forest_moisture > 100
+        {
+            return Err(NoiseError::InvalidBiomeThresholds);
+        }
+        Ok(())
+    }
+
+    fn biome(&self, elevation: u32, moisture: u32) -> Tile {
+        if elevation < self.sea_level {
+            Tile::Water
+        } else if elevation < self.beach_level {
+            Tile::Sand
+        } else if elevation >= self.mountain_level {
+            Tile::Mountain
+        } else if moisture >= self.forest_moisture {
+            Tile::Forest
+        } else {
+            Tile::Grass
+        }
+    }
+}
+
+pub fn from_params(params: &NoiseParams, mut seed: impl MapRng) -> Result<TileGrid, NoiseError> {
+    params.validate()?;
+
+    // elevation and moisture come from independent noise
+    let elevation = Fractal::new(params, &mut seed);
+    let moisture = Fractal::new(params, &mut seed);
+
+    let size = params.size;
+    let mut grid = TileGrid::new(size, Tile::Water);
+    for y in 0..size.height() {
+        for x in 0..size.width() {
+            let biome = params.biome(elevation.get(x, y), moisture.get(x, y));
+            grid.set(Point::new(x, y), biome);
+        }
+    }
+
+    Ok(grid)
+}
+
+// the eight gradients a lattice point can get, along the axes and diagonals
+const GRADIENTS: [(i64, i64); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (-1, 1),
+    (1, -1),
+    (-1, -1),
+];
+
+// gradient noise on a lattice, repeating every 256 lattice points
+struct Lattice {
+    permutation: Vec<u8>,
+}
+
+impl Lattice {
+    // a shuffle of every byte, so the whole rng state shapes the noise
+    fn new(rng: &mut impl MapRng) -> Self {
+        let mut permutation: Vec<u8> = (0..=u8::MAX).collect();
+        for i in (1..permutation.len()).rev() {
+            permutation.swap(i, rng.range(0, i as u32) as usize);
+        }
+        Lattice { permutation }
+    }
+
+    fn gradient(&self, x: u32, y: u32) -> (i64, i64) {
+        let hash = |value: u32| usize::from(self.permutation[(value & 0xff) as usize]);
+        let index = hash(hash(x) as u32 ^ y);
+        GRADIENTS[index & 7]
+    }
+
+    // Perlin noise at `(x, y)` for lattice points `scale` cells apart, in `-ONE..=ONE`
+    fn noise(&self, x: u32, y: u32, scale: u32) -> i64 {
+        let (cx, cy) = (x / scale, y / scale);
+        let fx = i64::from(x % scale) * ONE / i64::from(scale);
+        let fy = i64::from(y % scale) * ONE / i64::from(scale);
+        let corner = |dx: u32, dy: u32| {
+            let (gx, gy) = self.gradient(cx.wrapping_add(dx), cy.wrapping_add(dy));
+            gx * (fx - i64::from(dx) * ONE) + gy * (fy - i64::from(dy) * ONE)
+        };
+
+        let (tx, ty) = (fade(fx), fade(fy));
+        let top = lerp(corner(0, 0), corner(1, 0), tx);
+        let bottom = lerp(corner(0, 1), corner(1, 1), tx);
+        lerp(top, bottom, ty)
+    }
+}
+
+// octaves of noise, each at half the scale and half the weight of the one before
+struct Fractal {
+    octaves: Vec<Lattice>,
+    scale: u32,
+}
+
+impl Fractal {
+    fn new(params: &NoiseParams, rng: &mut impl MapRng) -> Self {
+        Fractal {
+            octaves: (0..params.octaves).map(|_| Lattice::new(rng)).collect(),
+            scale: params.scale,
+        }
+    }
+
+    // the noise at `(x, y)` as a percentage
+    fn get(&self, x: u32, y: u32) -> u32 {
+        let mut total = 0;
+        let mut squares = 0;
+        for (octave, lattice) in self.octaves.iter().enumerate() {
+            let weight = ONE >> octave;
+            let scale = (self.scale >> octave).max(1);
+            total += lattice.noise(x, y, scale) * weight;
+            squares += weight * weight;
+        }
+        // independent octaves partly cancel out, dividing by the root of the
+        // summed squared weights keeps the spread the same for any octave count
+        let noise = total * SPREAD_DEN / (isqrt(squares) * SPREAD_NUM);
+        ((noise + ONE) * 50 / ONE).clamp(0, 100) as u32
+    }
+}
+
+// a single octave stays within three quarters of `ONE` either way nearly
+// everywhere, that much is stretched to cover all percentages
+const SPREAD_NUM: i64 = 3;
+const SPREAD_DEN: i64 = 4;
+
+// largest root not above `value`
+fn isqrt(value: i64) -> i64 {
+    let mut root = value;
+    let mut next = (root + 1) / 2;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+    root
+}
+
+// `6t^5 - 15t^4 + 10t^3`, flat at lattice points so octaves blend without creases
+fn fade(t: i64) -> i64 {
+    let cube = t * t / ONE * t / ONE;
+    cube * (t * (6 * t - 15 * ONE) / ONE + 10 * ONE) / ONE
+}
+
+fn lerp(a: i64, b: i64, t: i64) -> i64 {
+    a + (b - a) * t / ONE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::MersenneTwister;
+
+    fn params() -> NoiseParams {
+        NoiseParams {
+            size: Size::new(60, 40),
+            octaves: 4,
+            scale: 16,
+            sea_level: 25,
+            beach_level: 35,
+            mountain_level: 80,
+            forest_moisture: 50,
+        }
+    }
+
+    #[test]
+    fn terrain_has_every_biome() {
+        let grid = from_params(&params(), MersenneTwister::new(7)).unwrap();
+        for biome in [
+            Tile::Water,
+            Tile::Sand,
+            Tile::Grass,
+            Tile::Forest,
+            Tile::Mountain,
+        ] {
+            assert!(grid.tiles().contains(&biome), "{biome:?}");
+        }
+
+        // biome tiles survive both the codec and rendering
+        assert_eq!(grid, TileGrid::decode(&grid.encode()).unwrap());
+        assert_eq!(grid, TileGrid::from_rendered(&grid.to_string()));
+    }
+
+    #[test]
+    fn noise_is_smooth() {
+        // neighbouring cells never jump by more than a fifth of the range
+        let params = params();
+        let noise = Fractal::new(&params, &mut MersenneTwister::new(7));
+        for y in 0..params.size.height() {
+            for x in 1..params.size.width() {
+                let step = noise.get(x, y).abs_diff(noise.get(x - 1, y));
+                assert!(step <= 20, "{x}, {y}: {step}");
+            }
+        }
+    }
+
+    #[test]
+    fn noise_covers_the_range() {
+        for octaves in [1, MAX_OCTAVES] {
+            let params = NoiseParams {
+                size: Size::new(128, 128),
+                octaves,
+                ..params()
+            };
+            let noise = Fractal::new(&params, &mut MersenneTwister::new(7));
+            let mut cells = vec![];
+            for y in 0..params.size.height() {
+                for x in 0..params.size.width() {
+                    cells.push(noise.get(x, y));
+                }
+            }
+            assert!(cells.iter().min().unwrap() <= &5, "{octaves}");
+            assert!(cells.iter().max().unwrap() >= &95, "{octaves}");
+        }
+    }
+
+    #[test]
+    fn biome_thresholds() {
+        let params = params();
+        assert_eq!(Tile::Water, params.biome(24, 90));
+        assert_eq!(Tile::Sand, params.biome(25, 90));
+        assert_eq!(Tile::Sand, params.biome(34, 0));
+        assert_eq!(Tile::Grass, params.biome(35, 49));
+        assert_eq!(Tile::Forest, params.biome(79, 50));
+        assert_eq!(Tile::Mountain, params.biome(80, 0));
+    }
+
+    #[test]
+    fn noise_params_validation() {
+        let invalid = [
+            (
+                NoiseParams {
+                    size: Size::new(0, 40),
+                    ..params()
+                },
+                NoiseError::TooSmall,
+            ),
+            (
+                NoiseParams {
+                    octaves: 7,
+                    ..params()
+                },
+                NoiseError::InvalidOctaves,
+            ),
+            (
+                NoiseParams {
+                    scale: 0,
+                    ..params()
+                },
+                NoiseError::InvalidNoiseScale,
+            ),
+            (
+                NoiseParams {
+                    beach_level: 20,
+                    ..params()
+                },
+                NoiseError::InvalidBiomeThresholds,
+            ),
+            (
+                NoiseParams {
+                    forest_moisture: 101,
+                    ..params()
+                },
+                NoiseError::InvalidBiomeThresholds,
+            ),
+        ];
+        for (params, err) in invalid {
+            assert_eq!(Err(err), params.validate());
+        }
+    }
+}
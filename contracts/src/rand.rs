@@ -1,49 +1,553 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RangeError {
+    #[error("Empty range: min {min} is greater than max {max}")]
+    Empty { min: u32, max: u32 },
+}
+
+// source of randomness for the map generators
+pub trait MapRng {
+    fn next_u32(&mut self) -> u32;
+
+    // uniform value in `min..=max`, or an error when the range is empty
+    fn try_range(&mut self, min: u32, max: u32) -> Result<u32, RangeError> {
+        if max < min {
+            return Err(RangeError::Empty { min, max });
+        }
+
+        let span = max - min;
+        if span == u32::MAX {
+            return Ok(self.next_u32());
+        }
+
+        // reject the low `2^32 % n` values so every residue is equally likely
+        let n = span + 1;
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let value = self.next_u32();
+            if value >= threshold {
+                return Ok(min + value % n);
+            }
+        }
+    }
+
+    // uniform value in `min..=max` for ranges known not to be empty; callers that
+    // can't rule an empty range out use `try_range`
+    fn range(&mut self, min: u32, max: u32) -> u32 {
+        debug_assert!(min <= max, "empty range {min}..={max}");
+        self.try_range(min, max).unwrap_or(min)
+    }
+}
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908b0df;
+const UPPER_MASK: u32 = 0x80000000;
+const LOWER_MASK: u32 = 0x7fffffff;
+
+// identifies the generator a map was seeded with, so a stored seed always
+// replays the same map even after the default generator changes
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorVersion {
+    // the twister of the original contract: seeded from the first four random
+    // bytes, skipping the `mt[i + 397]` term of the twist and picking ranges by
+    // plain modulo. No map was ever stored with it, since the original contract
+    // kept only rendered tiles; it is here so the maps that contract drew can
+    // still be replayed from their block's random bytes
+    V1,
+    // reference mt19937 seeded with `init_by_array`
+    V2,
+}
+
+impl GeneratorVersion {
+    pub const CURRENT: GeneratorVersion = GeneratorVersion::V2;
+
+    pub fn rng(self, seed: &[u8]) -> VersionedRng {
+        match self {
+            GeneratorVersion::V1 => VersionedRng::V1(LegacyMersenneTwister::from_bytes(seed)),
+            GeneratorVersion::V2 => VersionedRng::V2(MersenneTwister::from_bytes(seed)),
+        }
+    }
+}
+
+pub enum VersionedRng {
+    V1(LegacyMersenneTwister),
+    V2(MersenneTwister),
+}
+
+impl MapRng for VersionedRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            VersionedRng::V1(rng) => rng.generate(),
+            VersionedRng::V2(rng) => rng.generate(),
+        }
+    }
+
+    fn try_range(&mut self, min: u32, max: u32) -> Result<u32, RangeError> {
+        match self {
+            VersionedRng::V1(rng) => rng.try_range(min, max),
+            VersionedRng::V2(rng) => rng.try_range(min, max),
+        }
+    }
+}
+
+// MT19937 as published by Matsumoto and Nishimura (mt19937ar.c)
 pub struct MersenneTwister {
-    state: [u32; 624],
+    state: [u32; N],
     index: usize,
 }
 
 impl MersenneTwister {
+    // `init_genrand`
     pub fn new(seed: u32) -> MersenneTwister {
-        let mut state = [0; 624];
+        let mut state = [0; N];
         state[0] = seed;
-        for i in 1..624 {
+        for i in 1..N {
             let prev = state[i - 1];
-            let xored = prev ^ (prev >> 30);
-            state[i] = ((xored as u64 * 0x6c078965u64) as u32 + i as u32) & 0xffffffff;
+            state[i] = (prev ^ (prev >> 30))
+                .wrapping_mul(1812433253)
+                .wrapping_add(i as u32);
+        }
+        MersenneTwister { state, index: N }
+    }
+
+    // `init_by_array`
+    pub fn from_key(key: &[u32]) -> MersenneTwister {
+        let mut mt = MersenneTwister::new(19650218);
+        let state = &mut mt.state;
+
+        let mut i = 1;
+        let mut j = 0;
+        for _ in 0..N.max(key.len()) {
+            let prev = state[i - 1] ^ (state[i - 1] >> 30);
+            state[i] = (state[i] ^ prev.wrapping_mul(1664525))
+                .wrapping_add(key.get(j).copied().unwrap_or(0))
+                .wrapping_add(j as u32);
+            i += 1;
+            j += 1;
+            if i >= N {
+                state[0] = state[N - 1];
+                i = 1;
+            }
+            if j >= key.len() {
+                j = 0;
+            }
+        }
+        for _ in 0..N - 1 {
+            let prev = state[i - 1] ^ (state[i - 1] >> 30);
+            state[i] = (state[i] ^ prev.wrapping_mul(1566083941)).wrapping_sub(i as u32);
+            i += 1;
+            if i >= N {
+                state[0] = state[N - 1];
+                i = 1;
+            }
         }
-        MersenneTwister { state, index: 0 }
+        state[0] = UPPER_MASK;
+
+        mt
+    }
+
+    // seeds the generator from raw bytes (e.g. the 32 bytes of Secret VRF output),
+    // read as little-endian u32 words
+    pub fn from_bytes(bytes: &[u8]) -> MersenneTwister {
+        let key: Vec<u32> = bytes
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0u8; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u32::from_le_bytes(word)
+            })
+            .collect();
+        MersenneTwister::from_key(&key)
     }
 
     pub fn generate(&mut self) -> u32 {
-        if self.index == 0 {
+        if self.index >= N {
             self.twist();
+            self.index = 0;
         }
+        self.next_tempered()
+    }
+
+    pub fn generate_range(&mut self, min: u32, max: u32) -> u32 {
+        self.range(min, max)
+    }
 
+    fn next_tempered(&mut self) -> u32 {
         let mut value = self.state[self.index];
         value ^= value >> 11;
         value ^= (value << 7) & 0x9d2c5680;
         value ^= (value << 15) & 0xefc60000;
         value ^= value >> 18;
 
-        self.index = (self.index + 1) % 624;
+        self.index += 1;
         value
     }
 
-    pub fn generate_range(&mut self, min: u32, max: u32) -> u32 {
-        min + (self.generate() % (max - min + 1))
+    fn twist(&mut self) {
+        for i in 0..N {
+            let y = (self.state[i] & UPPER_MASK) | (self.state[(i + 1) % N] & LOWER_MASK);
+            let mag = if y & 1 != 0 { MATRIX_A } else { 0 };
+            self.state[i] = self.state[(i + M) % N] ^ (y >> 1) ^ mag;
+        }
+    }
+}
+
+impl MapRng for MersenneTwister {
+    fn next_u32(&mut self) -> u32 {
+        self.generate()
+    }
+}
+
+// the twister of `GeneratorVersion::V1`, bit for bit the one of the original contract
+pub struct LegacyMersenneTwister(MersenneTwister);
+
+impl LegacyMersenneTwister {
+    pub fn new(seed: u32) -> LegacyMersenneTwister {
+        LegacyMersenneTwister(MersenneTwister::new(seed))
+    }
+
+    // only the first four bytes, read as a little-endian u32, ever went into the seed
+    pub fn from_bytes(bytes: &[u8]) -> LegacyMersenneTwister {
+        let mut word = [0u8; 4];
+        let len = bytes.len().min(4);
+        word[..len].copy_from_slice(&bytes[..len]);
+        LegacyMersenneTwister::new(u32::from_le_bytes(word))
+    }
+
+    pub fn generate(&mut self) -> u32 {
+        if self.0.index >= N {
+            self.twist();
+            self.0.index = 0;
+        }
+        self.0.next_tempered()
     }
 
     fn twist(&mut self) {
-        for i in 0..624 {
-            let x = (self.state[i] as u64 & 0x80000000u64)
-                + (self.state[(i + 1) % 624] as u64 & 0x7fffffffu64);
-            let x_a = x >> 1;
-            if x % 2 != 0 {
-                self.state[i] = (x_a ^ 0x9908b0dfu64) as u32;
-            } else {
-                self.state[i] = x_a as u32;
+        let state = &mut self.0.state;
+        for i in 0..N {
+            let y = (state[i] & UPPER_MASK) | (state[(i + 1) % N] & LOWER_MASK);
+            let mag = if y & 1 != 0 { MATRIX_A } else { 0 };
+            state[i] = (y >> 1) ^ mag;
+        }
+    }
+}
+
+impl MapRng for LegacyMersenneTwister {
+    fn next_u32(&mut self) -> u32 {
+        self.generate()
+    }
+
+    // plain modulo, slightly biased towards low values, as the original contract did
+    fn try_range(&mut self, min: u32, max: u32) -> Result<u32, RangeError> {
+        if max < min {
+            return Err(RangeError::Empty { min, max });
+        }
+        match (max - min).checked_add(1) {
+            Some(n) => Ok(min + self.generate() % n),
+            None => Ok(self.generate()),
+        }
+    }
+}
+
+// PCG-XSH-RR 64/32 (pcg32) as described in https://www.pcg-random.org
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(init_state: u64, init_seq: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (init_seq << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(init_state);
+        rng.step();
+        rng
+    }
+
+    // seeds state and stream from the first 16 bytes, read as little-endian u64 words
+    pub fn from_bytes(bytes: &[u8]) -> Pcg32 {
+        let mut words = [0u8; 16];
+        let len = bytes.len().min(16);
+        words[..len].copy_from_slice(&bytes[..len]);
+
+        let mut init_state = [0u8; 8];
+        let mut init_seq = [0u8; 8];
+        init_state.copy_from_slice(&words[..8]);
+        init_seq.copy_from_slice(&words[8..]);
+        Pcg32::new(u64::from_le_bytes(init_state), u64::from_le_bytes(init_seq))
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(Pcg32::MULTIPLIER)
+            .wrapping_add(self.inc);
+    }
+}
+
+impl MapRng for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // replays a fixed sequence, for driving the generators deterministically
+    struct SequenceRng {
+        values: Vec<u32>,
+        index: usize,
+    }
+
+    impl MapRng for SequenceRng {
+        fn next_u32(&mut self) -> u32 {
+            let value = self.values[self.index % self.values.len()];
+            self.index += 1;
+            value
+        }
+    }
+
+    #[test]
+    fn mt19937_init_genrand_vectors() {
+        // std::mt19937 with the default seed; the 10000th output is required by the C++ standard
+        let mut rng = MersenneTwister::new(5489);
+        let expected = [
+            3499211612, 581869302, 3890346734, 3586334585, 545404204, 4161255391, 3922919429,
+            949333985, 2715962298, 1323567403,
+        ];
+        for value in expected {
+            assert_eq!(value, rng.generate());
+        }
+        for _ in 10..9999 {
+            rng.generate();
+        }
+        assert_eq!(4123659995, rng.generate());
+    }
+
+    #[test]
+    fn mt19937_init_by_array_vectors() {
+        // first outputs listed in the reference mt19937ar.out
+        let mut rng = MersenneTwister::from_key(&[0x123, 0x234, 0x345, 0x456]);
+        let expected = [
+            1067595299, 955945823, 477289528, 4107218783, 4228976476, 3344332714, 3355579695,
+            227628506, 810200273, 2591290167,
+        ];
+        for value in expected {
+            assert_eq!(value, rng.generate());
+        }
+    }
+
+    #[test]
+    fn mt19937_from_bytes() {
+        let bytes: Vec<u8> = [0x123u32, 0x234, 0x345, 0x456]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        let mut rng = MersenneTwister::from_bytes(&bytes);
+        assert_eq!(1067595299, rng.generate());
+    }
+
+    // the generator of the original contract, as it was written there
+    struct BaselineTwister {
+        state: [u32; 624],
+        index: usize,
+    }
+
+    impl BaselineTwister {
+        fn new(seed: u32) -> BaselineTwister {
+            let mut state = [0; 624];
+            state[0] = seed;
+            for i in 1..624 {
+                let prev = state[i - 1];
+                let xored = prev ^ (prev >> 30);
+                state[i] = ((xored as u64 * 0x6c078965u64) as u32).wrapping_add(i as u32);
             }
+            BaselineTwister { state, index: 0 }
+        }
+
+        fn generate(&mut self) -> u32 {
+            if self.index == 0 {
+                self.twist();
+            }
+
+            let mut value = self.state[self.index];
+            value ^= value >> 11;
+            value ^= (value << 7) & 0x9d2c5680;
+            value ^= (value << 15) & 0xefc60000;
+            value ^= value >> 18;
+
+            self.index = (self.index + 1) % 624;
+            value
+        }
+
+        fn generate_range(&mut self, min: u32, max: u32) -> u32 {
+            min + (self.generate() % (max - min + 1))
+        }
+
+        fn twist(&mut self) {
+            for i in 0..624 {
+                let x = (self.state[i] as u64 & 0x80000000u64)
+                    + (self.state[(i + 1) % 624] as u64 & 0x7fffffffu64);
+                let x_a = x >> 1;
+                if x & 1 != 0 {
+                    self.state[i] = (x_a ^ 0x9908b0dfu64) as u32;
+                } else {
+                    self.state[i] = x_a as u32;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn v1_matches_original_contract() {
+        let mut seed = [0u8; 32];
+        seed[..4].copy_from_slice(&0xdead_beefu32.to_le_bytes());
+        seed[4..].fill(9);
+
+        let mut v1 = GeneratorVersion::V1.rng(&seed);
+        let mut baseline = BaselineTwister::new(0xdead_beef);
+        for i in 0..2000 {
+            let max = i % 50 + 1;
+            assert_eq!(baseline.generate_range(0, max), v1.range(0, max));
+            assert_eq!(baseline.generate(), v1.next_u32());
+        }
+    }
+
+    #[test]
+    fn generator_versions() {
+        let seed = [7u8; 32];
+
+        let mut v2 = GeneratorVersion::V2.rng(&seed);
+        let mut reference = MersenneTwister::from_bytes(&seed);
+        let mut v1 = GeneratorVersion::V1.rng(&seed);
+        let mut legacy = LegacyMersenneTwister::from_bytes(&seed);
+        for _ in 0..1000 {
+            assert_eq!(reference.generate(), v2.next_u32());
+            assert_eq!(legacy.generate(), v1.next_u32());
+        }
+
+        let mut v1 = GeneratorVersion::V1.rng(&seed);
+        let mut v2 = GeneratorVersion::V2.rng(&seed);
+        assert_ne!(
+            (0..4).map(|_| v1.next_u32()).collect::<Vec<_>>(),
+            (0..4).map(|_| v2.next_u32()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn pcg32_known_answers() {
+        // first outputs of the reference `pcg32-demo` (seed 42, stream 54)
+        let mut rng = Pcg32::new(42, 54);
+        let expected = [
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+        for value in expected {
+            assert_eq!(value, rng.next_u32());
+        }
+    }
+
+    #[test]
+    fn pcg32_from_bytes() {
+        let mut bytes = [0u8; 32];
+        bytes[..8].copy_from_slice(&42u64.to_le_bytes());
+        bytes[8..16].copy_from_slice(&54u64.to_le_bytes());
+
+        let mut rng = Pcg32::from_bytes(&bytes);
+        assert_eq!(0xa15c02b7, rng.next_u32());
+    }
+
+    #[test]
+    fn range_rejects_biased_values() {
+        // 2^32 % 3 == 1, so 0 is the only rejected value for a span of 3
+        let mut rng = SequenceRng {
+            values: vec![0, 1, 2, 3, 4, 5],
+            index: 0,
+        };
+        let values: Vec<u32> = (0..5).map(|_| rng.range(10, 12)).collect();
+        assert_eq!(vec![11, 12, 10, 11, 12], values);
+
+        let mut rng = SequenceRng {
+            values: vec![u32::MAX - 1, u32::MAX],
+            index: 0,
+        };
+        assert_eq!(u32::MAX - 1, rng.range(0, u32::MAX));
+        assert_eq!(u32::MAX, rng.range(0, u32::MAX));
+    }
+
+    #[test]
+    fn try_range_empty() {
+        let mut rng = Pcg32::new(42, 54);
+        assert_eq!(
+            Err(RangeError::Empty { min: 3, max: 2 }),
+            rng.try_range(3, 2)
+        );
+        assert_eq!(
+            Err(RangeError::Empty {
+                min: u32::MAX,
+                max: 0
+            }),
+            rng.try_range(u32::MAX, 0)
+        );
+    }
+
+    #[test]
+    fn range_stays_within_bounds() {
+        let mut bounds = Pcg32::new(7, 11);
+        let mut rng = MersenneTwister::new(5489);
+
+        let edges = [
+            (0, 0),
+            (0, 1),
+            (0, u32::MAX),
+            (1, u32::MAX),
+            (u32::MAX - 1, u32::MAX),
+            (u32::MAX, u32::MAX),
+            (0, u32::MAX / 2 + 1),
+        ];
+        let random = (0..1000).map(|_| {
+            let a = bounds.next_u32() >> (bounds.next_u32() % 32);
+            let b = bounds.next_u32() >> (bounds.next_u32() % 32);
+            (a.min(b), a.max(b))
+        });
+
+        for (min, max) in edges.into_iter().chain(random) {
+            for _ in 0..8 {
+                let value = rng.try_range(min, max).unwrap();
+                assert!(min <= value && value <= max, "{value} not in {min}..={max}");
+            }
+            if min > 0 {
+                assert!(rng.try_range(min, min - 1).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn range_is_uniform() {
+        // a span of 3 * 2^30 is where modulo reduction is most biased
+        let mut rng = Pcg32::new(42, 54);
+        let n = 3 << 30;
+        let mut buckets = [0u32; 3];
+        for _ in 0..30_000 {
+            buckets[(rng.range(0, n - 1) >> 30) as usize] += 1;
+        }
+        for count in buckets {
+            assert!((9_000..11_000).contains(&count), "{buckets:?}");
         }
     }
 }
@@ -1,22 +1,631 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Storage};
+use cosmwasm_std::{Addr, Binary, BlockInfo, Coin, StdError, StdResult, Storage, Timestamp};
+
+use crate::bsp::{transpose, MapData, Rectangle, Size, TileGrid};
+use crate::error::ContractError;
+use crate::generator::{GeneratedMap, GeneratorError, GeneratorParams};
+use crate::msg::Order;
+use crate::rand::GeneratorVersion;
+use crate::wfc::Tileset;
 use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use secret_toolkit_crypto::sha_256;
+use secret_toolkit_serialization::{Bincode2, Json, Serde};
+use secret_toolkit_storage::{AppendStore, Item, Keymap, KeymapBuilder, WithoutIter};
 
+pub static CONTRACT_INFO_KEY: &[u8] = b"contract_info";
 pub static CONFIG_KEY: &[u8] = b"config";
+pub static MAP_COUNT_KEY: &[u8] = b"map_count";
+pub static MAPS_KEY: &[u8] = b"maps";
+pub static MAP_INFOS_KEY: &[u8] = b"map_infos";
+pub static OWNER_MAPS_KEY: &[u8] = b"owner_maps";
+pub static LIVE_MAP_COUNT_KEY: &[u8] = b"live_map_count";
+pub static VIEWERS_KEY: &[u8] = b"viewers";
+pub static LAST_GENERATED_KEY: &[u8] = b"last_generated";
+pub static TOKEN_COUNT_KEY: &[u8] = b"token_count";
+pub static OWNER_TOKENS_KEY: &[u8] = b"owner_tokens";
+pub static TOKEN_APPROVALS_KEY: &[u8] = b"token_approvals";
+pub static OPERATORS_KEY: &[u8] = b"operators";
+pub static RECEIVERS_KEY: &[u8] = b"receivers";
+pub static COLLECTED_FEES_KEY: &[u8] = b"collected_fees";
+pub static TILESETS_KEY: &[u8] = b"tilesets";
+pub static TILESET_CONTENTS_KEY: &[u8] = b"tileset_contents";
+pub static PERMITS_PREFIX: &str = "revoked_permits";
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 pub struct State {
+    pub count: i32,
+    pub owner: Addr,
+    #[serde(default)]
+    pub storage_mode: StorageMode,
+    // proposed by `TransferOwnership`, becomes the owner once it accepts
+    #[serde(default)]
+    pub pending_owner: Option<Addr>,
+    #[serde(default)]
+    pub admins: Vec<Addr>,
+    #[serde(default)]
+    pub paused: bool,
+    // charged for each `Generate`, which is free when unset
+    #[serde(default)]
+    pub fee: Option<Coin>,
+    #[serde(default)]
+    pub limits: Limits,
+    // maps with lower ids were removed by `Clear`, unless they were minted
+    #[serde(default)]
+    pub cleared_below: u32,
+    // bumped by every `Clear`, each one starts the owners' map lists afresh, apart
+    // from the tokens they hold
+    #[serde(default)]
+    pub map_epoch: u32,
+}
+
+// limits on `Generate`, unset limits are not enforced
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq, JsonSchema)]
+pub struct Limits {
+    // most maps a single owner may hold
+    pub max_maps_per_owner: Option<u32>,
+    // blocks an address has to wait between two `Generate` calls
+    pub min_blocks_between_generations: Option<u64>,
+}
+
+impl State {
+    // whether map `id` went away in a `Clear`; its entries are left in storage
+    // so that clearing takes the same gas however many maps there are
+    pub fn is_cleared(&self, storage: &dyn Storage, id: u32) -> bool {
+        id < self.cleared_below
+            && !MAP_INFOS
+                .get(storage, &id)
+                .is_some_and(|map_info| map_info.minted)
+    }
+
+    // the owner is implicitly an admin
+    pub fn is_admin(&self, address: &Addr) -> bool {
+        *address == self.owner || self.admins.contains(address)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageMode {
+    // store the generated tiles
+    #[default]
+    Tiles,
+    // store only the seed and parameters, maps are regenerated when queried and
+    // capped at `MAX_SEED_MAP_CELLS` to keep that affordable
+    Seed,
+}
+
+// `State` as stored before the maps moved out into their own keymap
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct LegacyState {
     pub count: i32,
     pub owner: Addr,
     pub maps: Vec<String>,
 }
 
-pub fn config(storage: &mut dyn Storage) -> Singleton<State> {
+pub fn config(storage: &mut dyn Storage) -> Singleton<'_, State> {
     singleton(storage, CONFIG_KEY)
 }
 
-pub fn config_read(storage: &dyn Storage) -> ReadonlySingleton<State> {
+pub fn config_read(storage: &dyn Storage) -> ReadonlySingleton<'_, State> {
+    singleton_read(storage, CONFIG_KEY)
+}
+
+pub fn legacy_config_read(storage: &dyn Storage) -> ReadonlySingleton<'_, LegacyState> {
     singleton_read(storage, CONFIG_KEY)
 }
+
+// the deployed contract and version, in the same layout as cw2
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct ContractVersion {
+    pub contract: String,
+    pub version: String,
+}
+
+// missing for contracts instantiated before versioning was introduced
+pub static CONTRACT_VERSION: Item<ContractVersion, Json> = Item::new(CONTRACT_INFO_KEY);
+
+// a stored map, tagged with the format it was written in
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum StoredMap {
+    // text rendered column-major, one line per x
+    V1(String),
+    // text rendered row-major, one line per y
+    V2(String),
+    // row-major text with the geometry and seed it was generated from
+    V3 {
+        map: String,
+        rooms: Vec<Rectangle>,
+        corridors: Vec<Rectangle>,
+        seed: Binary,
+        generator_version: GeneratorVersion,
+    },
+    // as V3, with the tiles in the compact `TileGrid` encoding
+    V4 {
+        tiles: Binary,
+        rooms: Vec<Rectangle>,
+        corridors: Vec<Rectangle>,
+        seed: Binary,
+        generator_version: GeneratorVersion,
+    },
+    // only what the map is generated from, see `StorageMode::Seed`
+    V5 {
+        seed: Binary,
+        generator_version: GeneratorVersion,
+        params: GeneratorParams,
+    },
+}
+
+// seed-only maps are built again on every read, which for wave function collapse
+// maps needs the tileset from `storage`
+impl StoredMap {
+    pub fn grid(&self, storage: &dyn Storage) -> Result<TileGrid, ContractError> {
+        match self {
+            StoredMap::V1(map) => Ok(TileGrid::from_rendered(&transpose(map))),
+            StoredMap::V2(map) | StoredMap::V3 { map, .. } => Ok(TileGrid::from_rendered(map)),
+            StoredMap::V4 { tiles, .. } => Ok(TileGrid::decode(tiles.as_slice())?),
+            StoredMap::V5 {
+                seed,
+                generator_version,
+                params,
+            } => Ok(regenerate(storage, seed, *generator_version, params)?.grid),
+        }
+    }
+
+    // the map as row-major text, whatever format it was stored in
+    pub fn render(&self, storage: &dyn Storage) -> Result<String, ContractError> {
+        Ok(self.grid(storage)?.to_string())
+    }
+
+    // the map in the compact `TileGrid` encoding
+    pub fn encode(&self, storage: &dyn Storage) -> Result<Binary, ContractError> {
+        match self {
+            StoredMap::V4 { tiles, .. } => Ok(tiles.clone()),
+            _ => Ok(Binary::from(self.grid(storage)?.encode())),
+        }
+    }
+
+    // the map's dimensions, without regenerating seed-only maps
+    pub fn size(&self, storage: &dyn Storage) -> Result<Size, ContractError> {
+        match self {
+            StoredMap::V5 { params, .. } => Ok(params.size()),
+            _ => Ok(self.grid(storage)?.size()),
+        }
+    }
+
+    // the seed the map was generated from, unknown for the text formats
+    pub fn seed(&self) -> Option<(Binary, GeneratorVersion)> {
+        match self {
+            StoredMap::V1(_) | StoredMap::V2(_) => None,
+            StoredMap::V3 {
+                seed,
+                generator_version,
+                ..
+            }
+            | StoredMap::V4 {
+                seed,
+                generator_version,
+                ..
+            }
+            | StoredMap::V5 {
+                seed,
+                generator_version,
+                ..
+            } => Some((seed.clone(), *generator_version)),
+        }
+    }
+
+    // the map in structured form, older formats come without geometry or seed
+    pub fn data(&self, storage: &dyn Storage) -> Result<MapData, ContractError> {
+        match self {
+            StoredMap::V1(_) | StoredMap::V2(_) => Ok(MapData::from_grid(self.grid(storage)?)),
+            StoredMap::V3 {
+                rooms,
+                corridors,
+                seed,
+                generator_version,
+                ..
+            }
+            | StoredMap::V4 {
+                rooms,
+                corridors,
+                seed,
+                generator_version,
+                ..
+            } => Ok(MapData {
+                rooms: rooms.clone(),
+                corridors: corridors.clone(),
+                seed: Some(seed.clone()),
+                generator_version: Some(*generator_version),
+                ..MapData::from_grid(self.grid(storage)?)
+            }),
+            StoredMap::V5 {
+                seed,
+                generator_version,
+                params,
+            } => regenerated_data(storage, seed, *generator_version, params.clone()),
+        }
+    }
+}
+
+// builds a map stored as seed and parameters again
+fn regenerate(
+    storage: &dyn Storage,
+    seed: &Binary,
+    generator_version: GeneratorVersion,
+    params: &GeneratorParams,
+) -> Result<GeneratedMap, GeneratorError> {
+    params.generate(storage, generator_version.rng(seed.as_slice()))
+}
+
+fn regenerated_data(
+    storage: &dyn Storage,
+    seed: &Binary,
+    generator_version: GeneratorVersion,
+    params: GeneratorParams,
+) -> Result<MapData, ContractError> {
+    let map = regenerate(storage, seed, generator_version, &params)?;
+    Ok(MapData {
+        rooms: map.rooms,
+        corridors: map.corridors,
+        seed: Some(seed.clone()),
+        generator_version: Some(generator_version),
+        generator: Some(params),
+        ..MapData::from_grid(map.grid)
+    })
+}
+
+// id of the next map, ids are assigned sequentially from 0 and never reused, not
+// even after a `Clear`
+pub static MAP_COUNT: Item<u32> = Item::new(MAP_COUNT_KEY);
+
+// number of maps neither deleted nor cleared
+pub static LIVE_MAP_COUNT: Item<u32> = Item::new(LIVE_MAP_COUNT_KEY);
+
+// one entry per map, keyed by map id
+pub static MAPS: Keymap<u32, StoredMap, Bincode2, WithoutIter> =
+    KeymapBuilder::new(MAPS_KEY).without_iter().build();
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct MapInfo {
+    pub creator: Addr,
+    // the current holder, the creator until the map is transferred
+    pub owner: Addr,
+    pub height: u64,
+    pub time: Timestamp,
+    // only the owner and the viewers they authorize can read the tiles
+    pub private: bool,
+    // whether the map is a SNIP-721 token that can be transferred
+    pub minted: bool,
+}
+
+// who generated and holds each map and when it was generated, keyed by map id
+pub static MAP_INFOS: Keymap<u32, MapInfo, Bincode2, WithoutIter> =
+    KeymapBuilder::new(MAP_INFOS_KEY).without_iter().build();
+
+// ids of the maps each owner holds that were generated since the last `Clear`,
+// kept sorted in ascending order; the tokens they kept through it are only in
+// their `OWNER_TOKENS`
+pub static OWNER_MAPS: AppendStore<u32> = AppendStore::new(OWNER_MAPS_KEY);
+
+// the lists of the first epoch keep the layout from before there were epochs
+pub fn owner_maps(epoch: u32, owner: &Addr) -> AppendStore<'static, u32> {
+    match epoch {
+        0 => OWNER_MAPS.add_suffix(owner.as_bytes()),
+        _ => OWNER_MAPS
+            .add_suffix(&epoch.to_be_bytes())
+            .add_suffix(owner.as_bytes()),
+    }
+}
+
+fn current_owner_maps(storage: &dyn Storage, owner: &Addr) -> StdResult<AppendStore<'static, u32>> {
+    Ok(owner_maps(config_read(storage).load()?.map_epoch, owner))
+}
+
+// all of an owner's maps: the tokens they kept through the last `Clear`, then the
+// maps of the current epoch, which all have higher ids
+fn owner_map_ids(storage: &dyn Storage, owner: &Addr) -> StdResult<IdList> {
+    let state = config_read(storage).load()?;
+    let tokens = owner_tokens(owner);
+    let kept = id_position(&tokens, storage, state.cleared_below)?;
+    let maps = owner_maps(state.map_epoch, owner);
+    let len = maps.get_len(storage)?;
+    Ok(IdList {
+        parts: vec![(tokens, kept), (maps, len)],
+    })
+}
+
+pub fn owner_map_count(storage: &dyn Storage, owner: &Addr) -> StdResult<u32> {
+    Ok(owner_map_ids(storage, owner)?.len())
+}
+
+// number of minted maps
+pub static TOKEN_COUNT: Item<u32> = Item::new(TOKEN_COUNT_KEY);
+
+// ids of the minted maps each owner holds, a sorted subset of their `OWNER_MAPS`
+pub static OWNER_TOKENS: AppendStore<u32> = AppendStore::new(OWNER_TOKENS_KEY);
+
+pub fn owner_tokens(owner: &Addr) -> AppendStore<'static, u32> {
+    OWNER_TOKENS.add_suffix(owner.as_bytes())
+}
+
+// position of the first id that is not less than `id`
+fn id_position(store: &AppendStore<u32>, storage: &dyn Storage, id: u32) -> StdResult<u32> {
+    let (mut low, mut high) = (0, store.get_len(storage)?);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if store.get_at(storage, mid)? < id {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+pub fn add_owner_map(storage: &mut dyn Storage, owner: &Addr, id: u32) -> StdResult<()> {
+    let store = current_owner_maps(storage, owner)?;
+    insert_id(storage, &store, id)
+}
+
+pub fn remove_owner_map(storage: &mut dyn Storage, owner: &Addr, id: u32) -> StdResult<()> {
+    let store = current_owner_maps(storage, owner)?;
+    remove_id(storage, &store, id)
+}
+
+pub fn add_owner_token(storage: &mut dyn Storage, owner: &Addr, id: u32) -> StdResult<()> {
+    insert_id(storage, &owner_tokens(owner), id)
+}
+
+pub fn remove_owner_token(storage: &mut dyn Storage, owner: &Addr, id: u32) -> StdResult<()> {
+    remove_id(storage, &owner_tokens(owner), id)
+}
+
+fn insert_id(storage: &mut dyn Storage, store: &AppendStore<u32>, id: u32) -> StdResult<()> {
+    let len = store.get_len(storage)?;
+    let position = id_position(store, storage, id)?;
+    if position == len {
+        return store.push(storage, &id);
+    }
+
+    // shift the larger ids up by one to keep the list sorted
+    let last = store.get_at(storage, len - 1)?;
+    store.push(storage, &last)?;
+    for i in (position + 1..len).rev() {
+        let previous = store.get_at(storage, i - 1)?;
+        store.set_at(storage, i, &previous)?;
+    }
+    store.set_at(storage, position, &id)
+}
+
+fn remove_id(storage: &mut dyn Storage, store: &AppendStore<u32>, id: u32) -> StdResult<()> {
+    let position = id_position(store, storage, id)?;
+    if position < store.get_len(storage)? && store.get_at(storage, position)? == id {
+        store.remove(storage, position)?;
+    }
+    Ok(())
+}
+
+// sorted ids spread over stores, taking the first so many ids of each, which all
+// have to be less than those taken from the next
+struct IdList {
+    parts: Vec<(AppendStore<'static, u32>, u32)>,
+}
+
+impl IdList {
+    fn single(storage: &dyn Storage, store: AppendStore<'static, u32>) -> StdResult<Self> {
+        let len = store.get_len(storage)?;
+        Ok(IdList {
+            parts: vec![(store, len)],
+        })
+    }
+
+    fn len(&self) -> u32 {
+        self.parts.iter().map(|(_, len)| len).sum()
+    }
+
+    fn get_at(&self, storage: &dyn Storage, mut position: u32) -> StdResult<u32> {
+        for (store, len) in &self.parts {
+            if position < *len {
+                return store.get_at(storage, position);
+            }
+            position -= len;
+        }
+        Err(StdError::not_found("id"))
+    }
+
+    // position of the first id that is not less than `id`
+    fn position(&self, storage: &dyn Storage, id: u32) -> StdResult<u32> {
+        let mut position = 0;
+        for (store, len) in &self.parts {
+            position += id_position(store, storage, id)?.min(*len);
+        }
+        Ok(position)
+    }
+}
+
+// one page of an owner's map ids, and whether more follow it
+pub fn owner_maps_page(
+    storage: &dyn Storage,
+    owner: &Addr,
+    start_after: Option<u32>,
+    limit: usize,
+    order: Order,
+) -> StdResult<(Vec<u32>, bool)> {
+    let ids = owner_map_ids(storage, owner)?;
+    ids_page(storage, &ids, start_after, limit, order)
+}
+
+// one page of an owner's token ids
+pub fn owner_tokens_page(
+    storage: &dyn Storage,
+    owner: &Addr,
+    start_after: Option<u32>,
+    limit: usize,
+) -> StdResult<Vec<u32>> {
+    let ids = IdList::single(storage, owner_tokens(owner))?;
+    Ok(ids_page(storage, &ids, start_after, limit, Order::Ascending)?.0)
+}
+
+fn ids_page(
+    storage: &dyn Storage,
+    ids: &IdList,
+    start_after: Option<u32>,
+    limit: usize,
+    order: Order,
+) -> StdResult<(Vec<u32>, bool)> {
+    let len = ids.len();
+
+    // one position past the page, to tell whether another page follows
+    let positions: Box<dyn Iterator<Item = u32>> = match order {
+        Order::Ascending => {
+            let start = match start_after {
+                Some(u32::MAX) => len,
+                Some(id) => ids.position(storage, id + 1)?,
+                None => 0,
+            };
+            Box::new((start..len).take(limit + 1))
+        }
+        Order::Descending => {
+            let end = match start_after {
+                Some(id) => ids.position(storage, id)?,
+                None => len,
+            };
+            Box::new((0..end).rev().take(limit + 1))
+        }
+    };
+
+    let mut page = positions
+        .map(|position| ids.get_at(storage, position))
+        .collect::<StdResult<Vec<u32>>>()?;
+    let more = page.len() > limit;
+    page.truncate(limit);
+    Ok((page, more))
+}
+
+// (owner, viewer) pairs where the owner let the viewer read their private maps
+pub static VIEWERS: Keymap<(Addr, Addr), bool, Bincode2, WithoutIter> =
+    KeymapBuilder::new(VIEWERS_KEY).without_iter().build();
+
+// whether `viewer` may read the tiles of map `id`; `None` is an anonymous query
+pub fn can_view(storage: &dyn Storage, id: u32, viewer: Option<&Addr>) -> bool {
+    match MAP_INFOS.get(storage, &id) {
+        Some(MapInfo {
+            owner,
+            private: true,
+            ..
+        }) => viewer.is_some_and(|viewer| {
+            *viewer == owner || VIEWERS.contains(storage, &(owner, viewer.clone()))
+        }),
+        // public maps and maps stored before creators were recorded
+        _ => true,
+    }
+}
+
+// when an approval lapses, as SNIP-721 has it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Expiration {
+    AtHeight(u64),
+    // seconds since the epoch
+    AtTime(u64),
+    Never,
+}
+
+impl Expiration {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        match self {
+            Expiration::AtHeight(height) => block.height >= *height,
+            Expiration::AtTime(time) => block.time.seconds() >= *time,
+            Expiration::Never => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct Approval {
+    pub spender: Addr,
+    pub expires: Expiration,
+}
+
+// whether `approvals` still let `spender` transfer
+pub fn is_approved(approvals: &[Approval], spender: &Addr, block: &BlockInfo) -> bool {
+    approvals
+        .iter()
+        .any(|approval| approval.spender == *spender && !approval.expires.is_expired(block))
+}
+
+// adds, replaces or with `expires` unset removes the approval of `spender`, and
+// drops the ones that lapsed
+pub fn set_approval(
+    approvals: &mut Vec<Approval>,
+    spender: Addr,
+    expires: Option<Expiration>,
+    block: &BlockInfo,
+) {
+    approvals.retain(|approval| approval.spender != spender && !approval.expires.is_expired(block));
+    if let Some(expires) = expires {
+        approvals.push(Approval { spender, expires });
+    }
+}
+
+// who the holder of each token let transfer it, cleared whenever it changes hands
+pub static TOKEN_APPROVALS: Keymap<u32, Vec<Approval>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(TOKEN_APPROVALS_KEY)
+        .without_iter()
+        .build();
+
+// who each address let transfer all of its tokens
+pub static OPERATORS: Keymap<Addr, Vec<Approval>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(OPERATORS_KEY).without_iter().build();
+
+// how `SendNft` calls back a contract that registered with `RegisterReceiveNft`
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct Receiver {
+    pub code_hash: String,
+    // takes all tokens of a send in one `BatchReceiveNft`
+    pub batch: bool,
+}
+
+pub static RECEIVERS: Keymap<Addr, Receiver, Bincode2, WithoutIter> =
+    KeymapBuilder::new(RECEIVERS_KEY).without_iter().build();
+
+// block height of each address's latest `Generate`
+pub static LAST_GENERATED: Keymap<Addr, u64, Bincode2, WithoutIter> =
+    KeymapBuilder::new(LAST_GENERATED_KEY)
+        .without_iter()
+        .build();
+
+// wave function collapse tilesets uploaded by admins, by name, as the hash the
+// tileset is stored under in `TILESET_CONTENTS`
+pub static TILESETS: Keymap<String, Binary, Bincode2, WithoutIter> =
+    KeymapBuilder::new(TILESETS_KEY).without_iter().build();
+
+// every tileset ever uploaded, by the sha256 of its serialization; entries are
+// never changed or removed, so maps can refer to their tileset by hash alone
+pub static TILESET_CONTENTS: Keymap<Binary, Tileset, Bincode2, WithoutIter> =
+    KeymapBuilder::new(TILESET_CONTENTS_KEY)
+        .without_iter()
+        .build();
+
+// stores `tileset` unless the same one is stored already, and returns its hash
+pub fn store_tileset(storage: &mut dyn Storage, tileset: &Tileset) -> StdResult<Binary> {
+    let hash = Binary::from(sha_256(&Bincode2::serialize(tileset)?).to_vec());
+    if !TILESET_CONTENTS.contains(storage, &hash) {
+        TILESET_CONTENTS.insert(storage, &hash, tileset)?;
+    }
+    Ok(hash)
+}
+
+// fees charged by `Generate` and not yet withdrawn, one coin per denom
+pub static COLLECTED_FEES: Item<Vec<Coin>> = Item::new(COLLECTED_FEES_KEY);
+
+pub fn add_collected_fee(storage: &mut dyn Storage, fee: &Coin) -> StdResult<()> {
+    let mut collected = COLLECTED_FEES.may_load(storage)?.unwrap_or_default();
+    match collected.iter_mut().find(|coin| coin.denom == fee.denom) {
+        Some(coin) => coin.amount += fee.amount,
+        None => collected.push(fee.clone()),
+    }
+    COLLECTED_FEES.save(storage, &collected)
+}
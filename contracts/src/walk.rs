@@ -0,0 +1,267 @@
+use crate::bsp::{Point, Size, Tile, TileGrid, MAX_MAP_SIZE};
+use crate::rand::MapRng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// which way walkers drift besides their random steps
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WalkBias {
+    Center,
+    Edges,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+pub struct WalkParams {
+    pub size: Size,
+    // share in percent of the cells inside the border to carve into floor
+    pub floor_percent: u32,
+    // number of walkers, all starting at the center
+    pub walkers: u32,
+    pub bias: Option<WalkBias>,
+}
+
+pub const MIN_WALK_SIZE: Size = Size::new(3, 3);
+pub const MAX_FLOOR_PERCENT: u32 = 90;
+pub const MAX_WALKERS: u32 = 16;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WalkError {
+    #[error(
+        "Size of a random-walk map needs to be greater than or equal {}",
+        MIN_WALK_SIZE
+    )]
+    TooSmall,
+    #[error(
+        "Size of a random-walk map needs to be less than or equal {}",
+        MAX_MAP_SIZE
+    )]
+    TooLarge,
+    #[error("Floor percentage must be at most {}.", MAX_FLOOR_PERCENT)]
+    FloorPercentTooLarge,
+    #[error("Number of walkers must be between 1 and {}.", MAX_WALKERS)]
+    InvalidWalkerCount,
+    #[error(
+        "Walkers ran out of steps, at most {} per floor cell and {} in total.",
+        MAX_STEPS_PER_FLOOR,
+        MAX_WALK_STEPS
+    )]
+    StepLimitReached,
+}
+
+// walkers give up after this many steps per floor cell asked for, a strong
+// bias can otherwise keep them from ever reaching far away cells
+pub const MAX_STEPS_PER_FLOOR: u64 = 32;
+// and after this many steps on any map, whatever its size
+pub const MAX_WALK_STEPS: u64 = 250_000;
+
+// a biased walker takes one step in this many along its bias
+const BIASED_STEP_ONE_IN: u32 = 8;
+
+// left, right, up, down; flipping the lowest bit reverses a direction
+const DIRECTIONS: [(i64, i64); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+impl WalkParams {
+    pub fn validate(&self) -> Result<(), WalkError> {
+        if !MIN_WALK_SIZE.fits_within(self.size) {
+            return Err(WalkError::TooSmall);
+        }
+        if !self.size.fits_within(MAX_MAP_SIZE) {
+            return Err(WalkError::TooLarge);
+        }
+        if self.floor_percent > MAX_FLOOR_PERCENT {
+            return Err(WalkError::FloorPercentTooLarge);
+        }
+        if self.walkers == 0 || self.walkers > MAX_WALKERS {
+            return Err(WalkError::InvalidWalkerCount);
+        }
+        Ok(())
+    }
+}
+
+pub fn from_params(params: &WalkParams, mut seed: impl MapRng) -> Result<TileGrid, WalkError> {
+    params.validate()?;
+
+    let size = params.size;
+    let interior = u64::from(size.width() - 2) * u64::from(size.height() - 2);
+    let target = (interior * u64::from(params.floor_percent) / 100).max(1);
+    let max_steps = (target * MAX_STEPS_PER_FLOOR).min(MAX_WALK_STEPS);
+
+    let mut grid = TileGrid::new(size, Tile::Wall);
+    let center = Point::new(size.width() / 2, size.height() / 2);
+    grid.set(center, Tile::Floor);
+    let mut floors = 1;
+
+    // walkers take turns, one step each
+    let mut walkers = vec![center; params.walkers as usize];
+    let mut steps = 0;
+    while floors < target && steps < max_steps {
+        for walker in walkers.iter_mut() {
+            *walker = step(*walker, center, size, params.bias, &mut seed);
+            steps += 1;
+            if grid.get(*walker) == Some(Tile::Wall) {
+                grid.set(*walker, Tile::Floor);
+                floors += 1;
+                if floors == target {
+                    break;
+                }
+            }
+        }
+    }
+
+    if floors < target {
+        return Err(WalkError::StepLimitReached);
+    }
+    Ok(grid)
+}
+
+// one step in a random direction, or now and then one along the bias;
+// walkers never step onto the border
+fn step(
+    point: Point,
+    center: Point,
+    size: Size,
+    bias: Option<WalkBias>,
+    rng: &mut impl MapRng,
+) -> Point {
+    let direction = match bias {
+        Some(bias) if rng.range(1, BIASED_STEP_ONE_IN) == 1 => {
+            biased_direction(point, center, bias)
+        }
+        _ => rng.range(0, 3) as usize,
+    };
+
+    let (dx, dy) = DIRECTIONS[direction];
+    let x = (i64::from(point.x()) + dx).clamp(1, i64::from(size.width()) - 2);
+    let y = (i64::from(point.y()) + dy).clamp(1, i64::from(size.height()) - 2);
+    Point::new(x as u32, y as u32)
+}
+
+// along the axis the walker is furthest out on, towards or away from the center
+fn biased_direction(point: Point, center: Point, bias: WalkBias) -> usize {
+    let dx = i64::from(point.x()) - i64::from(center.x());
+    let dy = i64::from(point.y()) - i64::from(center.y());
+    let towards_center = if dx.abs() >= dy.abs() {
+        if dx > 0 {
+            0
+        } else {
+            1
+        }
+    } else if dy > 0 {
+        2
+    } else {
+        3
+    };
+    match bias {
+        WalkBias::Center => towards_center,
+        WalkBias::Edges => towards_center ^ 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cave::keep_largest_region;
+    use crate::rand::MersenneTwister;
+
+    fn params() -> WalkParams {
+        WalkParams {
+            size: Size::new(40, 30),
+            floor_percent: 40,
+            walkers: 4,
+            bias: None,
+        }
+    }
+
+    fn floors(grid: &TileGrid) -> usize {
+        grid.tiles()
+            .iter()
+            .filter(|tile| **tile == Tile::Floor)
+            .count()
+    }
+
+    #[test]
+    fn walk_carves_target_floor_connected() {
+        let grid = from_params(&params(), MersenneTwister::new(7)).unwrap();
+        let size = grid.size();
+
+        // 40% of the 38 * 28 cells inside the border
+        assert_eq!(425, floors(&grid));
+
+        for x in 0..size.width() {
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(x, 0)));
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(x, size.height() - 1)));
+        }
+        for y in 0..size.height() {
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(0, y)));
+            assert_eq!(Some(Tile::Wall), grid.get(Point::new(size.width() - 1, y)));
+        }
+
+        let mut connected = grid.clone();
+        keep_largest_region(&mut connected);
+        assert_eq!(grid, connected);
+    }
+
+    #[test]
+    fn walk_bias() {
+        // floor cells on the two outermost rings inside the border
+        let near_edges = |grid: &TileGrid| {
+            let size = grid.size();
+            let mut cells = 0;
+            for y in 0..size.height() {
+                for x in 0..size.width() {
+                    let edge = x <= 2 || y <= 2 || x >= size.width() - 3 || y >= size.height() - 3;
+                    if edge && grid.get(Point::new(x, y)) == Some(Tile::Floor) {
+                        cells += 1;
+                    }
+                }
+            }
+            cells
+        };
+
+        let mut grids = vec![];
+        for bias in [Some(WalkBias::Center), None, Some(WalkBias::Edges)] {
+            let params = WalkParams { bias, ..params() };
+            let grid = from_params(&params, MersenneTwister::new(7)).unwrap();
+            assert_eq!(425, floors(&grid));
+            grids.push(near_edges(&grid));
+        }
+        assert!(grids[0] < grids[1]);
+        assert!(grids[1] < grids[2]);
+    }
+
+    #[test]
+    fn walk_runs_out_of_steps() {
+        // a walker pulled back to the center cannot carve most of a large map
+        let params = WalkParams {
+            size: Size::new(128, 128),
+            floor_percent: 90,
+            walkers: 1,
+            bias: Some(WalkBias::Center),
+        };
+        assert_eq!(
+            Err(WalkError::StepLimitReached),
+            from_params(&params, MersenneTwister::new(7))
+        );
+    }
+
+    #[test]
+    fn walk_params_validation() {
+        let invalid = [
+            (Size::new(2, 30), 40, 4, WalkError::TooSmall),
+            (Size::new(40, 30), 91, 4, WalkError::FloorPercentTooLarge),
+            (Size::new(40, 30), 40, 0, WalkError::InvalidWalkerCount),
+            (Size::new(40, 30), 40, 17, WalkError::InvalidWalkerCount),
+        ];
+        for (size, floor_percent, walkers, err) in invalid {
+            let params = WalkParams {
+                size,
+                floor_percent,
+                walkers,
+                bias: None,
+            };
+            assert_eq!(Err(err), params.validate());
+        }
+    }
+}
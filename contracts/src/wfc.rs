@@ -0,0 +1,441 @@
+use crate::bsp::{Point, Size, Tile, TileGrid};
+use crate::rand::MapRng;
+use cosmwasm_std::Binary;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, JsonSchema)]
+pub struct TilesetTile {
+    pub tile: Tile,
+    // relative chance of being picked when a cell collapses
+    pub weight: u32,
+}
+
+// tiles and the neighbours each one allows, referring to tiles by index
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct Tileset {
+    pub tiles: Vec<TilesetTile>,
+    // `(a, b)` allows `b` right of `a`
+    pub horizontal: Vec<(u32, u32)>,
+    // `(a, b)` allows `b` below `a`
+    pub vertical: Vec<(u32, u32)>,
+}
+
+pub const MAX_TILESET_TILES: usize = 64;
+// every pair of tiles once, in either direction
+pub const MAX_ADJACENCY_RULES: usize = MAX_TILESET_TILES * MAX_TILESET_TILES;
+pub const MAX_TILE_WEIGHT: u32 = 1000;
+
+// the whole grid is collapsed again from scratch this many times before giving up
+pub const WFC_ATTEMPTS: u32 = 10;
+
+// smaller than for the other generators, as every attempt propagates through
+// the whole grid
+pub const MAX_WFC_SIZE: Size = Size::new(64, 64);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WfcError {
+    #[error("Size of a wave function collapse map must not be empty.")]
+    TooSmall,
+    #[error(
+        "Size of a wave function collapse map needs to be less than or equal {}",
+        MAX_WFC_SIZE
+    )]
+    TooLarge,
+    #[error("A tileset needs between 1 and {} tiles.", MAX_TILESET_TILES)]
+    InvalidTilesetSize,
+    #[error("Tile weights must be between 1 and {}.", MAX_TILE_WEIGHT)]
+    InvalidTileWeight,
+    #[error("Adjacency rules may only refer to tiles in the tileset.")]
+    UnknownTilesetTile,
+    #[error(
+        "A tileset has at most {} adjacency rules in each direction.",
+        MAX_ADJACENCY_RULES
+    )]
+    TooManyAdjacencyRules,
+    #[error("Adjacency rules may not repeat a pair of tiles.")]
+    DuplicateAdjacencyRule,
+    #[error("No tileset is stored under hash {hash}.")]
+    UnknownTileset { hash: Binary },
+    #[error(
+        "Wave function collapse ran into a contradiction on every attempt, {attempts} in all."
+    )]
+    Contradiction { attempts: u32 },
+}
+
+impl Tileset {
+    pub fn validate(&self) -> Result<(), WfcError> {
+        if self.tiles.is_empty() || self.tiles.len() > MAX_TILESET_TILES {
+            return Err(WfcError::InvalidTilesetSize);
+        }
+        if self
+            .tiles
+            .iter()
+            .any(|tile| tile.weight == 0 || tile.weight > MAX_TILE_WEIGHT)
+        {
+            return Err(WfcError::InvalidTileWeight);
+        }
+        if self.horizontal.len() > MAX_ADJACENCY_RULES || self.vertical.len() > MAX_ADJACENCY_RULES
+        {
+            return Err(WfcError::TooManyAdjacencyRules);
+        }
+        let len = self.tiles.len() as u32;
+        if self
+            .horizontal
+            .iter()
+            .chain(self.vertical.iter())
+            .any(|(a, b)| *a >= len || *b >= len)
+        {
+            return Err(WfcError::UnknownTilesetTile);
+        }
+        for pairs in [&self.horizontal, &self.vertical] {
+            if pairs.iter().collect::<BTreeSet<_>>().len() != pairs.len() {
+                return Err(WfcError::DuplicateAdjacencyRule);
+            }
+        }
+        Ok(())
+    }
+}
+
+// the tileset is referred to by the hash it is stored under, tilesets are never
+// changed once stored so the map always replays the same
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+pub struct WfcParams {
+    pub size: Size,
+    pub tileset: Binary,
+}
+
+impl WfcParams {
+    pub fn validate(&self) -> Result<(), WfcError> {
+        if self.size.width() == 0 || self.size.height() == 0 {
+            return Err(WfcError::TooSmall);
+        }
+        if !self.size.fits_within(MAX_WFC_SIZE) {
+            return Err(WfcError::TooLarge);
+        }
+        Ok(())
+    }
+}
+
+// the tiles still possible in a cell, one bit per tileset tile
+type Options = u64;
+
+// neighbours allowed next to each tile, indexed by direction then tile
+struct Rules {
+    allowed: [Vec<Options>; 4],
+}
+
+// right, left, down, up; flipping the lowest bit reverses a direction
+const DIRECTIONS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+impl Rules {
+    fn new(tileset: &Tileset) -> Self {
+        let len = tileset.tiles.len();
+        let mut allowed = [vec![0; len], vec![0; len], vec![0; len], vec![0; len]];
+        for (direction, pairs) in [(0, &tileset.horizontal), (2, &tileset.vertical)] {
+            for (a, b) in pairs {
+                allowed[direction][*a as usize] |= 1 << b;
+                allowed[direction ^ 1][*b as usize] |= 1 << a;
+            }
+        }
+        Rules { allowed }
+    }
+
+    // every tile allowed next to any of `options`
+    fn neighbours(&self, options: Options, direction: usize) -> Options {
+        (0..self.allowed[direction].len())
+            .filter(|tile| options & (1 << tile) != 0)
+            .fold(0, |all, tile| all | self.allowed[direction][tile])
+    }
+}
+
+pub fn from_params(
+    params: &WfcParams,
+    tileset: &Tileset,
+    mut seed: impl MapRng,
+) -> Result<TileGrid, WfcError> {
+    params.validate()?;
+    tileset.validate()?;
+
+    let rules = Rules::new(tileset);
+    let width = params.size.width() as usize;
+    let height = params.size.height() as usize;
+    let mut start = Wave::new(Options::MAX >> (64 - tileset.tiles.len()), width, height);
+
+    // tiles that fit nowhere are ruled out before anything is picked, without any
+    // randomness involved a contradiction here would come up on every attempt
+    let mut stack: Vec<usize> = (0..start.cells.len()).collect();
+    if !start.propagate(&mut stack, &rules) {
+        return Err(WfcError::Contradiction { attempts: 1 });
+    }
+
+    for _ in 0..WFC_ATTEMPTS {
+        if let Some(cells) = collapse(start.clone(), tileset, &rules, &mut seed) {
+            let mut grid = TileGrid::new(params.size, Tile::Void);
+            for (i, options) in cells.into_iter().enumerate() {
+                let tile = tileset.tiles[options.trailing_zeros() as usize].tile;
+                grid.set(Point::new((i % width) as u32, (i / width) as u32), tile);
+            }
+            return Ok(grid);
+        }
+    }
+    Err(WfcError::Contradiction {
+        attempts: WFC_ATTEMPTS,
+    })
+}
+
+// the options of every cell, with the undecided cells bucketed by how many
+// options they have left so the next one to collapse is found without a scan
+#[derive(Clone)]
+struct Wave {
+    cells: Vec<Options>,
+    undecided: Vec<BTreeSet<usize>>,
+    width: usize,
+    height: usize,
+}
+
+impl Wave {
+    fn new(options: Options, width: usize, height: usize) -> Self {
+        let mut wave = Wave {
+            cells: vec![0; width * height],
+            undecided: vec![BTreeSet::new(); Options::BITS as usize + 1],
+            width,
+            height,
+        };
+        for cell in 0..wave.cells.len() {
+            wave.set(cell, options);
+        }
+        wave
+    }
+
+    fn set(&mut self, cell: usize, options: Options) {
+        let before = self.cells[cell].count_ones() as usize;
+        if before > 1 {
+            self.undecided[before].remove(&cell);
+        }
+        let after = options.count_ones() as usize;
+        if after > 1 {
+            self.undecided[after].insert(cell);
+        }
+        self.cells[cell] = options;
+    }
+
+    // the undecided cell with the fewest options, the first one on a tie
+    fn lowest_entropy(&self) -> Option<usize> {
+        self.undecided
+            .iter()
+            .find_map(|cells| cells.first().copied())
+    }
+
+    // narrows down the neighbours of the cells on `stack` until nothing changes,
+    // false once a cell has no options left
+    fn propagate(&mut self, stack: &mut Vec<usize>, rules: &Rules) -> bool {
+        let (width, height) = (self.width as i64, self.height as i64);
+        while let Some(cell) = stack.pop() {
+            let (x, y) = ((cell % self.width) as i64, (cell / self.width) as i64);
+            for (direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+                let neighbour = (ny * width + nx) as usize;
+                let options = self.cells[neighbour];
+                let narrowed = options & rules.neighbours(self.cells[cell], direction);
+                if narrowed == 0 {
+                    return false;
+                }
+                if narrowed != options {
+                    self.set(neighbour, narrowed);
+                    stack.push(neighbour);
+                }
+            }
+        }
+        true
+    }
+}
+
+// one attempt at collapsing every cell of `wave` to a single tile, `None` on a
+// contradiction
+fn collapse(
+    mut wave: Wave,
+    tileset: &Tileset,
+    rules: &Rules,
+    rng: &mut impl MapRng,
+) -> Option<Vec<Options>> {
+    let tiles = &tileset.tiles;
+    while let Some(cell) = wave.lowest_entropy() {
+        let options = wave.cells[cell];
+        let total: u32 = (0..tiles.len())
+            .filter(|tile| options & (1 << tile) != 0)
+            .map(|tile| tiles[tile].weight)
+            .sum();
+        let mut pick = rng.range(0, total - 1);
+        for tile in (0..tiles.len()).filter(|tile| options & (1 << tile) != 0) {
+            if pick < tiles[tile].weight {
+                wave.set(cell, 1 << tile);
+                break;
+            }
+            pick -= tiles[tile].weight;
+        }
+
+        if !wave.propagate(&mut vec![cell], rules) {
+            return None;
+        }
+    }
+    Some(wave.cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::MersenneTwister;
+
+    // floor only next to floor or wall, and void only next to wall
+    fn tileset() -> Tileset {
+        let pairs = vec![(0, 0), (0, 1), (1, 0), (1, 1), (1, 2), (2, 1), (2, 2)];
+        Tileset {
+            tiles: vec![
+                TilesetTile {
+                    tile: Tile::Floor,
+                    weight: 5,
+                },
+                TilesetTile {
+                    tile: Tile::Wall,
+                    weight: 2,
+                },
+                TilesetTile {
+                    tile: Tile::Void,
+                    weight: 3,
+                },
+            ],
+            horizontal: pairs.clone(),
+            vertical: pairs,
+        }
+    }
+
+    fn params(size: Size) -> WfcParams {
+        WfcParams {
+            size,
+            tileset: Binary::default(),
+        }
+    }
+
+    #[test]
+    fn wfc_follows_adjacency_rules() {
+        let params = params(Size::new(20, 15));
+        let grid = from_params(&params, &tileset(), MersenneTwister::new(7)).unwrap();
+        let size = grid.size();
+
+        let tiles = grid.tiles();
+        assert!(tiles.contains(&Tile::Floor));
+        assert!(tiles.contains(&Tile::Void));
+        for y in 0..size.height() {
+            for x in 0..size.width() {
+                let tile = grid.get(Point::new(x, y));
+                for neighbour in [
+                    grid.get(Point::new(x + 1, y)),
+                    grid.get(Point::new(x, y + 1)),
+                ] {
+                    assert!(!matches!(
+                        (tile, neighbour),
+                        (Some(Tile::Floor), Some(Tile::Void))
+                            | (Some(Tile::Void), Some(Tile::Floor))
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wfc_rules_are_directional() {
+        // wall never left of floor, and columns of a single tile
+        let tileset = Tileset {
+            tiles: vec![
+                TilesetTile {
+                    tile: Tile::Floor,
+                    weight: 1,
+                },
+                TilesetTile {
+                    tile: Tile::Wall,
+                    weight: 1,
+                },
+            ],
+            horizontal: vec![(0, 0), (0, 1), (1, 1)],
+            vertical: vec![(0, 0), (1, 1)],
+        };
+        let grid =
+            from_params(&params(Size::new(6, 4)), &tileset, MersenneTwister::new(7)).unwrap();
+        let rendered = grid.to_string();
+        let rows: Vec<&str> = rendered.lines().collect();
+        assert_eq!(4, rows.len());
+        for row in &rows {
+            assert_eq!(rows[0], *row);
+            assert!(!row.contains("10"));
+        }
+    }
+
+    #[test]
+    fn wfc_reports_contradictions() {
+        // floor needs wall to its right and wall allows nothing there, so no row of
+        // three fits, which shows before anything is picked
+        let tileset = Tileset {
+            tiles: vec![
+                TilesetTile {
+                    tile: Tile::Floor,
+                    weight: 1,
+                },
+                TilesetTile {
+                    tile: Tile::Wall,
+                    weight: 1,
+                },
+            ],
+            horizontal: vec![(0, 1)],
+            vertical: vec![],
+        };
+        let res = from_params(&params(Size::new(3, 1)), &tileset, MersenneTwister::new(7));
+        assert_eq!(Some(WfcError::Contradiction { attempts: 1 }), res.err());
+
+        // every tile has neighbours on all sides, but going right then down never
+        // ends on the same tile as going down then right
+        let tile = |tile| TilesetTile { tile, weight: 1 };
+        let tileset = Tileset {
+            tiles: vec![tile(Tile::Floor), tile(Tile::Wall), tile(Tile::Void)],
+            horizontal: vec![(0, 1), (1, 2), (2, 0)],
+            vertical: vec![(0, 1), (1, 0), (2, 2)],
+        };
+        let res = from_params(&params(Size::new(2, 2)), &tileset, MersenneTwister::new(7));
+        assert_eq!(
+            Some(WfcError::Contradiction {
+                attempts: WFC_ATTEMPTS
+            }),
+            res.err()
+        );
+    }
+
+    #[test]
+    fn tileset_validation() {
+        let mut tileset = tileset();
+        tileset.tiles[1].weight = 0;
+        assert_eq!(Err(WfcError::InvalidTileWeight), tileset.validate());
+
+        let mut tileset = self::tileset();
+        tileset.vertical.push((0, 3));
+        assert_eq!(Err(WfcError::UnknownTilesetTile), tileset.validate());
+
+        let mut tileset = self::tileset();
+        tileset.tiles.clear();
+        assert_eq!(Err(WfcError::InvalidTilesetSize), tileset.validate());
+
+        let mut tileset = self::tileset();
+        tileset.horizontal.push((2, 1));
+        assert_eq!(Err(WfcError::DuplicateAdjacencyRule), tileset.validate());
+
+        let mut tileset = self::tileset();
+        tileset.vertical = vec![(0, 0); MAX_ADJACENCY_RULES + 1];
+        assert_eq!(Err(WfcError::TooManyAdjacencyRules), tileset.validate());
+
+        assert_eq!(Err(WfcError::TooSmall), params(Size::new(0, 15)).validate());
+    }
+}
@@ -22,17 +22,25 @@ export const useSecretjs = () => {
       return;
     }
 
-    const tx = (await secretjs.query.compute.queryContract({
-      contract_address: contractAddress,
-      code_hash: contractCodeHash,
-      query: {
-        get_maps: {},
-      },
-    })) as { maps: string[] };
+    const maps: string[] = [];
+    let startAfter: number | null = null;
 
-    setMaps(tx.maps);
+    do {
+      const tx = (await secretjs.query.compute.queryContract({
+        contract_address: contractAddress,
+        code_hash: contractCodeHash,
+        query: {
+          get_maps: { start_after: startAfter },
+        },
+      })) as { maps: { index: number; map: string }[]; next: number | null };
 
-    return tx.maps;
+      maps.push(...tx.maps.map(({ map }) => map));
+      startAfter = tx.next;
+    } while (startAfter !== null);
+
+    setMaps(maps);
+
+    return maps;
   }, [secretAddress, secretjs]);
 
   const generate = useCallback(